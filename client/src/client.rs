@@ -0,0 +1,272 @@
+use crate::types::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The request could not be sent or its body could not be decoded.
+    Http(reqwest::Error),
+    /// The server answered with an error envelope.
+    Api(ErrorBody),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Http(e) => write!(f, "HTTP error: {}", e),
+            Error::Api(body) => write!(f, "{} {}: {}", body.status, body.code, body.message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Http(e)
+    }
+}
+
+/// Async client for one AI Index server.
+#[derive(Debug, Clone)]
+pub struct Client {
+    base_url: String,
+    http: reqwest::Client,
+    api_key: Option<String>,
+}
+
+impl Client {
+    /// A client for the server at `base_url`, e.g. `http://localhost:8080`.
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self::with_http(base_url, reqwest::Client::new())
+    }
+
+    /// Like [`Client::new`], reusing a configured `reqwest::Client`.
+    pub fn with_http(base_url: impl Into<String>, http: reqwest::Client) -> Self {
+        let base_url = base_url.into().trim_end_matches('/').to_string();
+        Client {
+            base_url,
+            http,
+            api_key: None,
+        }
+    }
+
+    /// Authenticate with an API key, as the admin routes require.
+    pub fn with_api_key(mut self, key: impl Into<String>) -> Self {
+        self.api_key = Some(key.into());
+        self
+    }
+
+    pub async fn index(&self) -> Result<IndexSnapshot, Error> {
+        self.get("/api/v1/index", &()).await
+    }
+
+    /// The latest `limit` snapshots, newest first.
+    pub async fn index_history(&self, limit: i64) -> Result<Vec<IndexSnapshot>, Error> {
+        self.get("/api/v1/index/history", &[("limit", limit)]).await
+    }
+
+    /// Index OHLC bars, oldest first.
+    pub async fn index_bars(&self, query: &BarsQuery) -> Result<Vec<OhlcBar>, Error> {
+        self.get("/api/v1/index/history", &with_interval(query))
+            .await
+    }
+
+    pub async fn stocks(&self) -> Result<Vec<StockDetail>, Error> {
+        self.get("/api/v1/stocks", &()).await
+    }
+
+    pub async fn stock(&self, symbol: &str) -> Result<StockDetail, Error> {
+        self.get(&format!("/api/v1/stocks/{}", segment(symbol)), &())
+            .await
+    }
+
+    /// The latest `limit` quotes for a symbol, newest first.
+    pub async fn stock_history(&self, symbol: &str, limit: i64) -> Result<Vec<PriceRecord>, Error> {
+        let path = format!("/api/v1/stocks/{}/history", segment(symbol));
+        self.get(&path, &[("limit", limit)]).await
+    }
+
+    /// OHLC bars for a symbol, oldest first.
+    pub async fn stock_bars(&self, symbol: &str, query: &BarsQuery) -> Result<Vec<OhlcBar>, Error> {
+        let path = format!("/api/v1/stocks/{}/history", segment(symbol));
+        self.get(&path, &with_interval(query)).await
+    }
+
+    /// OHLC bars for a symbol with its index weight and contribution.
+    pub async fn stock_weighted_bars(
+        &self,
+        symbol: &str,
+        query: &BarsQuery,
+    ) -> Result<Vec<StockBar>, Error> {
+        #[derive(Serialize)]
+        struct Query {
+            #[serde(flatten)]
+            bars: BarsQuery,
+            weights: bool,
+        }
+        let path = format!("/api/v1/stocks/{}/history", segment(symbol));
+        let query = Query {
+            bars: with_interval(query),
+            weights: true,
+        };
+        self.get(&path, &query).await
+    }
+
+    pub async fn sectors(&self) -> Result<Vec<SectorSummary>, Error> {
+        self.get("/api/v1/sectors", &()).await
+    }
+
+    /// Latest `limit` prices per benchmark, newest first. For ranges or bars,
+    /// call [`Client::stock_bars`] with the benchmark symbol.
+    pub async fn benchmark_history(&self, limit: i64) -> Result<BenchmarkHistory, Error> {
+        self.get("/api/v1/benchmarks/history", &[("limit", limit)])
+            .await
+    }
+
+    pub async fn config(&self) -> Result<ConfigInfo, Error> {
+        self.get("/api/v1/config", &()).await
+    }
+
+    /// Recent fetcher runs, newest first, optionally of one kind.
+    pub async fn fetch_runs(
+        &self,
+        kind: Option<&str>,
+        limit: Option<i64>,
+    ) -> Result<Vec<FetchRun>, Error> {
+        #[derive(Serialize)]
+        struct Query<'a> {
+            #[serde(skip_serializing_if = "Option::is_none")]
+            kind: Option<&'a str>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            limit: Option<i64>,
+        }
+        self.get("/api/v1/admin/fetch-runs", &Query { kind, limit })
+            .await
+    }
+
+    pub async fn backups(&self) -> Result<Vec<BackupInfo>, Error> {
+        self.get("/api/v1/admin/backups", &()).await
+    }
+
+    pub async fn create_backup(&self) -> Result<BackupInfo, Error> {
+        self.post("/api/v1/admin/backups", &()).await
+    }
+
+    /// Start a quote cycle now.
+    pub async fn run_quotes(&self) -> Result<Job, Error> {
+        self.post("/api/v1/admin/jobs/quotes", &()).await
+    }
+
+    /// Refresh every profile now.
+    pub async fn run_profiles(&self) -> Result<Job, Error> {
+        self.post("/api/v1/admin/jobs/profiles", &()).await
+    }
+
+    /// Backfill daily history, then recompute the affected snapshots.
+    pub async fn run_backfill(&self, request: &BackfillRequest) -> Result<Job, Error> {
+        self.post("/api/v1/admin/jobs/backfill", request).await
+    }
+
+    /// Recompute index snapshots from stored prices.
+    pub async fn run_recompute(&self, request: &RecomputeRequest) -> Result<Job, Error> {
+        self.post("/api/v1/admin/jobs/recompute", request).await
+    }
+
+    /// Re-read the server's stocks.toml and fetch data for added symbols.
+    /// Index history before the current session is left alone, so the index
+    /// steps when constituents change and the job's summary says a full
+    /// recompute is required; follow with [`Client::run_recompute`] without
+    /// `since` to rewrite history with the new constituents.
+    pub async fn run_reload(&self) -> Result<Job, Error> {
+        self.post("/api/v1/admin/jobs/reload", &()).await
+    }
+
+    /// Recent jobs, newest first.
+    pub async fn jobs(&self) -> Result<Vec<Job>, Error> {
+        self.get("/api/v1/admin/jobs", &()).await
+    }
+
+    pub async fn job(&self, id: u64) -> Result<Job, Error> {
+        self.get(&format!("/api/v1/admin/jobs/{}", id), &()).await
+    }
+
+    async fn get<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &impl Serialize,
+    ) -> Result<T, Error> {
+        let request = self.request(reqwest::Method::GET, path).query(query);
+        decode(request.send().await?).await
+    }
+
+    async fn post<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &impl Serialize,
+    ) -> Result<T, Error> {
+        let request = self.request(reqwest::Method::POST, path).json(body);
+        decode(request.send().await?).await
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        let request = self
+            .http
+            .request(method, format!("{}{}", self.base_url, path));
+        match &self.api_key {
+            Some(key) => request.bearer_auth(key),
+            None => request,
+        }
+    }
+}
+
+/// Percent-encode a value for use as one path segment.
+fn segment(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Bars are only returned when an interval or bound is present.
+fn with_interval(query: &BarsQuery) -> BarsQuery {
+    BarsQuery {
+        interval: Some(query.interval.clone().unwrap_or_else(|| "1d".to_string())),
+        ..query.clone()
+    }
+}
+
+async fn decode<T: DeserializeOwned>(response: reqwest::Response) -> Result<T, Error> {
+    if response.status().is_success() {
+        return Ok(response.json().await?);
+    }
+    let status = response.status();
+    match response.json::<ErrorEnvelope>().await {
+        Ok(envelope) => Err(Error::Api(envelope.error)),
+        // Not from the API itself, e.g. a proxy error page.
+        Err(_) => Err(Error::Api(ErrorBody {
+            status: status.as_u16(),
+            code: "http".to_string(),
+            message: status.to_string(),
+            request_id: None,
+        })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn symbols_are_encoded_as_one_segment() {
+        assert_eq!(segment("NVDA"), "NVDA");
+        assert_eq!(segment("BRK.B"), "BRK.B");
+        assert_eq!(segment("BF/B"), "BF%2FB");
+        assert_eq!(segment("A B?"), "A%20B%3F");
+    }
+}
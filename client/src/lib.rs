@@ -0,0 +1,22 @@
+//! Typed client for the AI Index API.
+//!
+//! [`types`] holds the request and response shapes, shared with the server
+//! so both sides change together. With the default `http` feature,
+//! [`Client`] wraps each endpoint in a typed async method.
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), aiindex_client::Error> {
+//! let client = aiindex_client::Client::new("http://localhost:8080");
+//! let index = client.index().await?;
+//! println!("{} at {}", index.value, index.timestamp);
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod types;
+
+#[cfg(feature = "http")]
+mod client;
+
+#[cfg(feature = "http")]
+pub use client::{Client, Error};
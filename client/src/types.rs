@@ -0,0 +1,330 @@
+//! Request and response types of the AI Index API.
+//!
+//! The server serializes these same types, so a field change here is a
+//! change to the wire format.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[cfg(feature = "openapi")]
+use utoipa::ToSchema;
+
+/// A stored quote for one symbol.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct PriceRecord {
+    pub symbol: String,
+    pub price: f64,
+    pub change: Option<f64>,
+    pub change_pct: Option<f64>,
+    pub market_cap: Option<f64>,
+    pub timestamp: String,
+}
+
+/// A computed index snapshot.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct IndexSnapshot {
+    pub value: f64,
+    pub daily_change: Option<f64>,
+    pub daily_change_pct: Option<f64>,
+    pub timestamp: String,
+}
+
+/// A resampled OHLC bar returned by history endpoints.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct OhlcBar {
+    /// Start of the bar.
+    pub timestamp: String,
+    /// Timestamp of the last point in the bar. Server-side only.
+    #[serde(skip)]
+    #[cfg_attr(feature = "openapi", schema(ignore))]
+    pub closed_at: String,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub volume: Option<f64>,
+}
+
+/// A stock's OHLC bar with its index weight and contribution at the close.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct StockBar {
+    #[serde(flatten)]
+    pub bar: OhlcBar,
+    /// Always present, and null before the stock had traded. Required so a
+    /// plain bar never decodes as a weighted one.
+    #[serde(deserialize_with = "Option::deserialize")]
+    #[cfg_attr(feature = "openapi", schema(required = true))]
+    pub weight: Option<f64>,
+    /// Index points the stock accounts for: weight × close / base price × base value.
+    #[serde(deserialize_with = "Option::deserialize")]
+    #[cfg_attr(feature = "openapi", schema(required = true))]
+    pub contribution: Option<f64>,
+}
+
+/// Body of `/api/index/history`: bars when `from`, `to` or `interval` is
+/// given, otherwise the latest snapshots, newest first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+#[serde(untagged)]
+pub enum IndexHistory {
+    Bars(Vec<OhlcBar>),
+    Snapshots(Vec<IndexSnapshot>),
+}
+
+/// Body of `/api/stocks/{symbol}/history`, in the same forms as
+/// [`IndexHistory`]; `weights=true` adds weight and contribution to bars.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+#[serde(untagged)]
+pub enum StockHistory {
+    WeightedBars(Vec<StockBar>),
+    Bars(Vec<OhlcBar>),
+    Prices(Vec<PriceRecord>),
+}
+
+/// Stock detail returned by the API.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct StockDetail {
+    pub symbol: String,
+    pub sector: String,
+    pub sector_label: String,
+    pub sector_color: Option<String>,
+    pub sector_short_label: Option<String>,
+    pub sector_description: Option<String>,
+    pub price: f64,
+    pub change: Option<f64>,
+    pub change_pct: Option<f64>,
+    pub market_cap: Option<f64>,
+    pub weight: Option<f64>,
+    pub timestamp: String,
+    pub name: Option<String>,
+    pub exchange: Option<String>,
+    pub industry: Option<String>,
+    pub weburl: Option<String>,
+    pub logo: Option<String>,
+    pub country: Option<String>,
+}
+
+/// Sector summary returned by the API, in configured order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct SectorSummary {
+    pub key: String,
+    pub label: String,
+    /// CSS hex color for charts, if configured.
+    pub color: Option<String>,
+    pub short_label: Option<String>,
+    pub description: Option<String>,
+    pub symbols: Vec<String>,
+    pub total_weight: f64,
+    pub avg_change_pct: f64,
+}
+
+/// A single price point for benchmark history.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct BenchmarkPricePoint {
+    pub price: f64,
+    pub timestamp: String,
+}
+
+/// Benchmark price history keyed by symbol, newest first.
+pub type BenchmarkHistory = HashMap<String, Vec<BenchmarkPricePoint>>;
+
+/// A recorded quote, profile or backfill run, returned by /api/admin/fetch-runs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct FetchRun {
+    pub id: i64,
+    pub kind: String,
+    /// One of `running`, `ok`, `partial` or `failed`.
+    pub status: String,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    pub symbols: Vec<String>,
+    pub succeeded: i64,
+    pub failed: i64,
+    pub errors: Vec<String>,
+}
+
+/// An admin-triggered fetch, backfill or recompute, returned by
+/// /api/admin/jobs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct Job {
+    pub id: u64,
+    /// One of `quotes`, `profiles`, `backfill`, `recompute` or `reload`.
+    pub kind: String,
+    /// One of `queued`, `running`, `succeeded` or `failed`.
+    pub status: String,
+    pub created_at: String,
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+    /// Outcome summary, or the error for a failed job.
+    pub message: Option<String>,
+}
+
+/// Body of POST /api/admin/jobs/backfill. Optional: an empty body backfills
+/// every symbol over the last year.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct BackfillRequest {
+    /// Start of the window, inclusive: RFC 3339 or `YYYY-MM-DD`. The last
+    /// year when neither bound is given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    /// End of the window, exclusive: RFC 3339 or `YYYY-MM-DD`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    /// Symbols to backfill; every configured symbol when absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symbols: Option<Vec<String>>,
+}
+
+/// Body of POST /api/admin/jobs/recompute. Optional: an empty body
+/// recomputes all history.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct RecomputeRequest {
+    /// First session to recompute, `YYYY-MM-DD`; all history when absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<String>,
+}
+
+/// Config info returned by /api/config.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct ConfigInfo {
+    pub base_value: f64,
+    pub market_cap_weight_pct: u32,
+    pub index_stock_count: usize,
+    pub benchmark_symbols: Vec<String>,
+    /// How the server is running. Absent from servers that predate it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub runtime: Option<RuntimeSettings>,
+}
+
+/// The server's runtime settings, with secrets replaced by `[redacted]`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct RuntimeSettings {
+    pub bind_addr: String,
+    pub cors_origins: Vec<String>,
+    /// `sqlite` or `postgres`. The URL itself is not reported: its host,
+    /// user and connection options are nobody else's business.
+    pub database_backend: String,
+    pub database_max_connections: Option<u32>,
+    /// The stocks.toml in use; the built-in one when absent.
+    pub stocks_path: Option<String>,
+    /// `[redacted]` when a key is configured.
+    pub finnhub_api_key: Option<String>,
+    pub fetcher: FetcherSettings,
+    pub compaction: CompactionSettings,
+    pub backups: BackupSettings,
+    pub features: FeatureSettings,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct FetcherSettings {
+    pub enabled: bool,
+    pub quote_interval_secs: u64,
+    pub profile_interval_secs: u64,
+    /// Pause between provider calls.
+    pub call_spacing_ms: u64,
+    pub backfill_on_start: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct CompactionSettings {
+    pub enabled: bool,
+    pub price_retention_days: i64,
+    pub snapshot_retention_days: i64,
+    pub interval_hours: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct BackupSettings {
+    pub dir: String,
+    pub keep: usize,
+    /// 0 when scheduled backups are off.
+    pub interval_hours: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct FeatureSettings {
+    pub stream: bool,
+    pub export: bool,
+    pub legacy_routes: bool,
+}
+
+/// A backup file in the backup directory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct BackupInfo {
+    pub file: String,
+    pub size_bytes: u64,
+}
+
+/// Body of every failed request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct ErrorEnvelope {
+    pub error: ErrorBody,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct ErrorBody {
+    /// HTTP status code.
+    pub status: u16,
+    /// Stable machine-readable code, e.g. `not_found` or `no_data`.
+    pub code: String,
+    pub message: String,
+    /// Matches the `x-request-id` response header.
+    pub request_id: Option<String>,
+}
+
+/// Query for the bar forms of the history endpoints.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BarsQuery {
+    /// RFC 3339 timestamp or `YYYY-MM-DD`, inclusive.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    /// RFC 3339 timestamp or `YYYY-MM-DD`, exclusive.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    /// One of 15m, 1h, 1d (default), 1w or 1mo.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stock_history_keeps_plain_bars_apart_from_weighted_ones() {
+        let bar = r#"{"timestamp": "2024-03-11T13:30:00+00:00",
+            "open": 1.0, "high": 2.0, "low": 0.5, "close": 1.5, "volume": null"#;
+
+        let plain = format!("[{}}}]", bar);
+        let history: StockHistory = serde_json::from_str(&plain).unwrap();
+        assert!(matches!(history, StockHistory::Bars(_)));
+
+        let weighted = format!(r#"[{}, "weight": null, "contribution": null}}]"#, bar);
+        let history: StockHistory = serde_json::from_str(&weighted).unwrap();
+        assert!(matches!(history, StockHistory::WeightedBars(_)));
+    }
+}
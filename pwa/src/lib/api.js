@@ -1,35 +1,85 @@
-const BASE = '';
+const BASE = '/api/v1';
 
+// Failed requests carry {"error": {status, code, message, request_id}}.
+class ApiError extends Error {
+  constructor(status, body) {
+    super(body?.error?.message ?? `${status}`);
+    this.status = status;
+    this.code = body?.error?.code;
+    this.requestId = body?.error?.request_id;
+  }
+}
+
+// Always revalidate: a cached copy is answered with a cheap 304 until the
+// next fetch cycle, and a stream event must never be followed by stale data.
 async function fetchJson(path) {
-  const res = await fetch(`${BASE}${path}`);
-  if (!res.ok) throw new Error(`${res.status} ${res.statusText}`);
+  const res = await fetch(`${BASE}${path}`, { cache: 'no-cache' });
+  if (!res.ok) {
+    const body = await res.json().catch(() => null);
+    throw new ApiError(res.status, body);
+  }
   return res.json();
 }
 
-export function getIndex() {
-  return fetchJson('/api/index');
+// The latest snapshot, or null before the first one is computed.
+export async function getIndex() {
+  try {
+    return await fetchJson('/index');
+  } catch (e) {
+    if (e.code === 'no_data') return null;
+    throw e;
+  }
 }
 
 export function getIndexHistory(limit = 100) {
-  return fetchJson(`/api/index/history?limit=${limit}`);
+  return fetchJson(`/index/history?limit=${limit}`);
+}
+
+// OHLC bars for a window; interval is one of 15m, 1h, 1d, 1w, 1mo.
+export function getIndexBars({ from, to, interval = '1d' } = {}) {
+  const params = new URLSearchParams({ interval });
+  if (from) params.set('from', from);
+  if (to) params.set('to', to);
+  return fetchJson(`/index/history?${params}`);
 }
 
 export function getStocks() {
-  return fetchJson('/api/stocks');
+  return fetchJson('/stocks');
 }
 
 export function getStock(symbol) {
-  return fetchJson(`/api/stocks/${symbol}`);
+  return fetchJson(`/stocks/${symbol}`);
+}
+
+// OHLC bars for one stock; weights adds its index weight and contribution.
+export function getStockBars(symbol, { from, to, interval = '1d', weights = false } = {}) {
+  const params = new URLSearchParams({ interval });
+  if (from) params.set('from', from);
+  if (to) params.set('to', to);
+  if (weights) params.set('weights', 'true');
+  return fetchJson(`/stocks/${symbol}/history?${params}`);
 }
 
 export function getSectors() {
-  return fetchJson('/api/sectors');
+  return fetchJson('/sectors');
 }
 
 export function getBenchmarkHistory(limit = 10000) {
-  return fetchJson(`/api/benchmarks/history?limit=${limit}`);
+  return fetchJson(`/benchmarks/history?limit=${limit}`);
 }
 
 export function getConfig() {
-  return fetchJson('/api/config');
+  return fetchJson('/config');
+}
+
+// Live events from /api/stream; topics are snapshot, quote and fetch.
+// Returns the EventSource so callers can close() it. The stream needs a read
+// key, which EventSource cannot send, so without a key-injecting proxy it
+// fails with 401 and callers must keep polling.
+export function subscribe(topics, onEvent) {
+  const source = new EventSource(`${BASE}/stream?topics=${topics.join(',')}`);
+  for (const topic of topics) {
+    source.addEventListener(topic, (e) => onEvent(topic, JSON.parse(e.data)));
+  }
+  return source;
 }
@@ -0,0 +1,4 @@
+// Re-embed migrations when a new one is added without touching Rust sources.
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+}
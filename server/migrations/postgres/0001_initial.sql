@@ -0,0 +1,39 @@
+-- Baseline schema, mirroring migrations/sqlite/0001_initial.sql.
+-- Timestamps stay RFC 3339 text so both backends sort and compare them alike.
+
+CREATE TABLE IF NOT EXISTS prices (
+    id BIGSERIAL PRIMARY KEY,
+    symbol TEXT NOT NULL,
+    price DOUBLE PRECISION NOT NULL,
+    change DOUBLE PRECISION,
+    change_pct DOUBLE PRECISION,
+    market_cap DOUBLE PRECISION,
+    timestamp TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_prices_symbol_ts ON prices(symbol, timestamp);
+
+CREATE TABLE IF NOT EXISTS index_snapshots (
+    id BIGSERIAL PRIMARY KEY,
+    value DOUBLE PRECISION NOT NULL,
+    daily_change DOUBLE PRECISION,
+    daily_change_pct DOUBLE PRECISION,
+    timestamp TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS base_prices (
+    symbol TEXT PRIMARY KEY,
+    price DOUBLE PRECISION NOT NULL,
+    recorded_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS stock_profiles (
+    symbol TEXT PRIMARY KEY,
+    name TEXT,
+    exchange TEXT,
+    industry TEXT,
+    weburl TEXT,
+    logo TEXT,
+    country TEXT,
+    updated_at TEXT NOT NULL
+);
@@ -0,0 +1,10 @@
+-- Daily bars produced by intraday compaction keep the session's range.
+-- NULL on raw points, where open = high = low = price.
+
+ALTER TABLE prices ADD COLUMN open DOUBLE PRECISION;
+ALTER TABLE prices ADD COLUMN high DOUBLE PRECISION;
+ALTER TABLE prices ADD COLUMN low DOUBLE PRECISION;
+
+ALTER TABLE index_snapshots ADD COLUMN open DOUBLE PRECISION;
+ALTER TABLE index_snapshots ADD COLUMN high DOUBLE PRECISION;
+ALTER TABLE index_snapshots ADD COLUMN low DOUBLE PRECISION;
@@ -0,0 +1,15 @@
+-- One row per quote, profile or backfill run, for diagnosing a stalled fetcher.
+-- symbols and errors are JSON arrays of strings.
+
+CREATE TABLE fetch_runs (
+    id BIGSERIAL PRIMARY KEY,
+    kind TEXT NOT NULL,
+    started_at TEXT NOT NULL,
+    finished_at TEXT,
+    symbols TEXT NOT NULL,
+    succeeded BIGINT NOT NULL DEFAULT 0,
+    failed BIGINT NOT NULL DEFAULT 0,
+    errors TEXT NOT NULL DEFAULT '[]'
+);
+
+CREATE INDEX idx_fetch_runs_started ON fetch_runs(started_at);
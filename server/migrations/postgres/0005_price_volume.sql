@@ -0,0 +1,3 @@
+-- Traded volume from imported OHLCV bars. NULL for live quotes.
+
+ALTER TABLE prices ADD COLUMN volume DOUBLE PRECISION;
@@ -0,0 +1,14 @@
+-- API keys for the admin routes. Only a SHA-256 hash of each key is stored;
+-- prefix is the key's first characters, to tell keys apart in listings.
+-- role is 'read' or 'admin'.
+
+CREATE TABLE api_keys (
+    id BIGSERIAL PRIMARY KEY,
+    name TEXT NOT NULL,
+    prefix TEXT NOT NULL,
+    key_hash TEXT NOT NULL UNIQUE,
+    role TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    last_used_at TEXT,
+    revoked_at TEXT
+);
@@ -0,0 +1,10 @@
+-- A single counter bumped whenever stored history is rewritten (recompute,
+-- backfill, import, compaction), so cached responses built from the old
+-- history stop validating.
+
+CREATE TABLE data_version (
+    id INTEGER PRIMARY KEY,
+    version BIGINT NOT NULL
+);
+
+INSERT INTO data_version (id, version) VALUES (1, 0);
@@ -0,0 +1,39 @@
+-- Baseline schema. Uses IF NOT EXISTS so databases created before
+-- migrations were introduced adopt it without changes.
+
+CREATE TABLE IF NOT EXISTS prices (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    symbol TEXT NOT NULL,
+    price REAL NOT NULL,
+    change REAL,
+    change_pct REAL,
+    market_cap REAL,
+    timestamp TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_prices_symbol_ts ON prices(symbol, timestamp);
+
+CREATE TABLE IF NOT EXISTS index_snapshots (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    value REAL NOT NULL,
+    daily_change REAL,
+    daily_change_pct REAL,
+    timestamp TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS base_prices (
+    symbol TEXT PRIMARY KEY,
+    price REAL NOT NULL,
+    recorded_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS stock_profiles (
+    symbol TEXT PRIMARY KEY,
+    name TEXT,
+    exchange TEXT,
+    industry TEXT,
+    weburl TEXT,
+    logo TEXT,
+    country TEXT,
+    updated_at TEXT NOT NULL
+);
@@ -0,0 +1,10 @@
+-- Daily bars produced by intraday compaction keep the session's range.
+-- NULL on raw points, where open = high = low = price.
+
+ALTER TABLE prices ADD COLUMN open REAL;
+ALTER TABLE prices ADD COLUMN high REAL;
+ALTER TABLE prices ADD COLUMN low REAL;
+
+ALTER TABLE index_snapshots ADD COLUMN open REAL;
+ALTER TABLE index_snapshots ADD COLUMN high REAL;
+ALTER TABLE index_snapshots ADD COLUMN low REAL;
@@ -0,0 +1,14 @@
+-- One price per (symbol, timestamp) and one snapshot per timestamp, so
+-- re-running backfill or overlapping it with live fetches is idempotent.
+-- Existing duplicates keep their most recently inserted row.
+
+DELETE FROM prices
+WHERE id NOT IN (SELECT MAX(id) FROM prices GROUP BY symbol, timestamp);
+
+DROP INDEX IF EXISTS idx_prices_symbol_ts;
+CREATE UNIQUE INDEX idx_prices_symbol_ts ON prices(symbol, timestamp);
+
+DELETE FROM index_snapshots
+WHERE id NOT IN (SELECT MAX(id) FROM index_snapshots GROUP BY timestamp);
+
+CREATE UNIQUE INDEX idx_index_snapshots_ts ON index_snapshots(timestamp);
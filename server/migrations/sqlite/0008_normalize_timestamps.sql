@@ -0,0 +1,35 @@
+-- Timestamps written by older builds or tools spell some instants
+-- differently from chrono's RFC 3339 (`Z` instead of `+00:00`, a space
+-- instead of `T`, all-zero fractional seconds), so 0003 could not see them
+-- as duplicates. Deduplicate on the normalized form, keeping the most
+-- recently inserted row, then store every timestamp in that form.
+
+DELETE FROM prices
+WHERE id NOT IN (
+    SELECT MAX(id) FROM prices
+    GROUP BY symbol, replace(replace(replace(replace(
+        CASE WHEN timestamp LIKE '%Z' THEN substr(timestamp, 1, length(timestamp) - 1) || '+00:00' ELSE timestamp END,
+        ' ', 'T'), '.000000000+', '+'), '.000000+', '+'), '.000+', '+')
+);
+
+UPDATE prices SET timestamp = replace(replace(replace(replace(
+        CASE WHEN timestamp LIKE '%Z' THEN substr(timestamp, 1, length(timestamp) - 1) || '+00:00' ELSE timestamp END,
+        ' ', 'T'), '.000000000+', '+'), '.000000+', '+'), '.000+', '+')
+WHERE timestamp <> replace(replace(replace(replace(
+        CASE WHEN timestamp LIKE '%Z' THEN substr(timestamp, 1, length(timestamp) - 1) || '+00:00' ELSE timestamp END,
+        ' ', 'T'), '.000000000+', '+'), '.000000+', '+'), '.000+', '+');
+
+DELETE FROM index_snapshots
+WHERE id NOT IN (
+    SELECT MAX(id) FROM index_snapshots
+    GROUP BY replace(replace(replace(replace(
+        CASE WHEN timestamp LIKE '%Z' THEN substr(timestamp, 1, length(timestamp) - 1) || '+00:00' ELSE timestamp END,
+        ' ', 'T'), '.000000000+', '+'), '.000000+', '+'), '.000+', '+')
+);
+
+UPDATE index_snapshots SET timestamp = replace(replace(replace(replace(
+        CASE WHEN timestamp LIKE '%Z' THEN substr(timestamp, 1, length(timestamp) - 1) || '+00:00' ELSE timestamp END,
+        ' ', 'T'), '.000000000+', '+'), '.000000+', '+'), '.000+', '+')
+WHERE timestamp <> replace(replace(replace(replace(
+        CASE WHEN timestamp LIKE '%Z' THEN substr(timestamp, 1, length(timestamp) - 1) || '+00:00' ELSE timestamp END,
+        ' ', 'T'), '.000000000+', '+'), '.000000+', '+'), '.000+', '+');
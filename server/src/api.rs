@@ -0,0 +1,180 @@
+//! HTTP routing and API versions.
+//!
+//! Each version is a router of relative paths nested under `/api/vN`, so a
+//! later version can start from the previous one's handlers and swap out
+//! only the routes whose shape changes. The unversioned `/api/*` paths from
+//! before versioning are aliases of v1 that announce their retirement with
+//! `Deprecation`, `Sunset` and successor `Link` headers.
+
+use crate::auth;
+use crate::error;
+use crate::http_cache;
+use crate::openapi;
+use crate::routes::{self, AppState};
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderValue, Method};
+use axum::middleware::{self, Next};
+use axum::response::Response;
+use axum::routing::{any, get, post};
+use axum::Router;
+use chrono::{NaiveDate, NaiveTime};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+/// Retirement schedule for a set of routes.
+#[derive(Debug, Clone, Copy)]
+pub struct Deprecation {
+    /// When the routes were deprecated (midnight UTC).
+    pub since: NaiveDate,
+    /// When they stop being served.
+    pub sunset: NaiveDate,
+    /// Prefix of the replacement routes.
+    pub successor: &'static str,
+}
+
+/// The pre-versioning `/api/*` aliases.
+const LEGACY: Deprecation = Deprecation {
+    since: NaiveDate::from_ymd_opt(2026, 10, 18).unwrap(),
+    sunset: NaiveDate::from_ymd_opt(2027, 4, 30).unwrap(),
+    successor: "/api/v1",
+};
+
+/// Every enabled route, with request IDs on all of them.
+pub fn router(state: AppState) -> Router {
+    let mut router = Router::new()
+        // Operational endpoints stay unversioned.
+        .route("/api/health", get(routes::health))
+        .route("/api/metrics", get(routes::get_metrics))
+        .nest("/api/v1", v1(&state))
+        .route("/api", any(error::not_found))
+        .route("/api/{*path}", any(error::not_found));
+    if state.settings.features.legacy_routes {
+        router = router.nest(
+            "/api",
+            v1(&state).layer(middleware::from_fn_with_state(LEGACY, deprecated)),
+        );
+    }
+    router
+        .with_state(state)
+        .layer(middleware::from_fn(error::request_id))
+}
+
+/// API version 1.
+pub fn v1(state: &AppState) -> Router<AppState> {
+    // Served from the latest-state cache and validated against it.
+    let data = Router::new()
+        .route("/index", get(routes::get_index))
+        .route("/index/history", get(routes::get_index_history))
+        .route("/stocks", get(routes::get_stocks))
+        .route("/stocks/{symbol}", get(routes::get_stock))
+        .route("/stocks/{symbol}/history", get(routes::get_stock_history))
+        .route("/sectors", get(routes::get_sectors))
+        .route("/benchmarks/history", get(routes::get_benchmark_history))
+        .route("/config", get(routes::get_config))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            http_cache::conditional,
+        ));
+
+    let admin = Router::new()
+        .route("/admin/fetch-runs", get(routes::get_fetch_runs))
+        .route(
+            "/admin/backups",
+            get(routes::list_backups).post(routes::create_backup),
+        )
+        .route("/admin/jobs", get(routes::list_jobs))
+        .route("/admin/jobs/{id}", get(routes::get_job))
+        .route("/admin/jobs/quotes", post(routes::run_quotes))
+        .route("/admin/jobs/profiles", post(routes::run_profiles))
+        .route("/admin/jobs/backfill", post(routes::run_backfill))
+        .route("/admin/jobs/recompute", post(routes::run_recompute))
+        .route("/admin/jobs/reload", post(routes::run_reload))
+        .layer(middleware::from_fn_with_state(
+            state.stores.clone(),
+            auth::require_key,
+        ))
+        .layer(middleware::map_response(http_cache::no_store));
+
+    // Live and bulk feeds are not cached and need a read key.
+    let mut feeds = Router::new();
+    let features = &state.settings.features;
+    if features.stream {
+        feeds = feeds.route("/stream", get(routes::stream));
+    }
+    if features.export {
+        feeds = feeds.route("/export/{dataset}", get(routes::export_dataset));
+    }
+    let feeds = feeds.layer(middleware::from_fn_with_state(
+        state.stores.clone(),
+        auth::require_key,
+    ));
+
+    Router::new()
+        .merge(data)
+        .merge(admin)
+        .merge(feeds)
+        .route("/openapi.json", get(openapi::spec))
+}
+
+/// CORS for the listed origins, or for any origin when the list is `*`.
+/// An empty list allows same-origin requests only.
+/// Credentials travel in the `Authorization` header, never in cookies.
+pub fn cors(origins: &[String]) -> CorsLayer {
+    let allow_origin = if origins.iter().any(|o| o == "*") {
+        AllowOrigin::any()
+    } else {
+        let origins: Vec<HeaderValue> = origins
+            .iter()
+            .map(|o| o.trim_end_matches('/'))
+            .filter_map(|o| match HeaderValue::from_str(o) {
+                Ok(v) => Some(v),
+                Err(_) => {
+                    tracing::warn!("Ignoring invalid CORS origin '{}'", o);
+                    None
+                }
+            })
+            .collect();
+        AllowOrigin::list(origins)
+    };
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([Method::GET, Method::HEAD, Method::POST])
+        .allow_headers([
+            header::AUTHORIZATION,
+            header::CONTENT_TYPE,
+            header::IF_NONE_MATCH,
+            header::IF_MODIFIED_SINCE,
+            error::REQUEST_ID_HEADER.clone(),
+        ])
+        .expose_headers([
+            header::ETAG,
+            header::LAST_MODIFIED,
+            error::REQUEST_ID_HEADER.clone(),
+        ])
+}
+
+/// Mark responses from deprecated routes, pointing at the successor path.
+async fn deprecated(State(dep): State<Deprecation>, req: Request, next: Next) -> Response {
+    // Nested routers see the path without their prefix.
+    let successor = format!(
+        "<{}{}>; rel=\"successor-version\"",
+        dep.successor,
+        req.uri().path()
+    );
+    let mut response = next.run(req).await;
+
+    let midnight = |date: NaiveDate| date.and_time(NaiveTime::MIN).and_utc();
+    let headers = [
+        (
+            "deprecation",
+            format!("@{}", midnight(dep.since).timestamp()),
+        ),
+        ("sunset", http_cache::http_date(midnight(dep.sunset))),
+        ("link", successor),
+    ];
+    for (name, value) in headers {
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            response.headers_mut().insert(name, value);
+        }
+    }
+    response
+}
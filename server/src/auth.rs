@@ -0,0 +1,184 @@
+//! API keys and the role check on admin routes.
+//!
+//! Keys are random tokens shown once when minted with `aiindex-server keys
+//! create`; the database keeps only their SHA-256 hashes. Requests present a
+//! key as `Authorization: Bearer <key>`. `read` keys may call the admin GET
+//! routes; anything that changes state needs an `admin` key.
+
+use crate::error::ApiError;
+use crate::models::ApiKey;
+use crate::store::{StoreResult, Stores};
+use axum::extract::{Request, State};
+use axum::http::{header, Method};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, TimeDelta, Utc};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
+
+/// Marks a string as an API key, e.g. in leaked-secret scanners.
+const KEY_PREFIX: &str = "aiidx_";
+
+/// Characters of the key kept in the clear for listings.
+const DISPLAY_PREFIX_LEN: usize = 12;
+
+/// How stale `last_used_at` may get before a request updates it.
+const TOUCH_INTERVAL: TimeDelta = TimeDelta::minutes(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    Read,
+    Admin,
+}
+
+impl Role {
+    pub fn name(self) -> &'static str {
+        match self {
+            Role::Read => "read",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "read" => Ok(Role::Read),
+            "admin" => Ok(Role::Admin),
+            other => Err(format!("unknown role '{}' (expected read or admin)", other)),
+        }
+    }
+}
+
+/// Mint a key and store its hash. Returns the stored key and the secret,
+/// which cannot be recovered later.
+pub async fn create_key(stores: &Stores, name: &str, role: Role) -> StoreResult<(ApiKey, String)> {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    let secret = format!("{}{}", KEY_PREFIX, hex::encode(bytes));
+    let prefix = &secret[..DISPLAY_PREFIX_LEN];
+    let created_at = Utc::now().to_rfc3339();
+
+    let id = stores
+        .keys
+        .create(name, prefix, &hash(&secret), role.name(), &created_at)
+        .await?;
+    let key = ApiKey {
+        id,
+        name: name.to_string(),
+        prefix: prefix.to_string(),
+        role: role.name().to_string(),
+        created_at,
+        last_used_at: None,
+        revoked_at: None,
+    };
+    Ok((key, secret))
+}
+
+fn hash(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
+
+/// Reject requests without a key of sufficient role: `read` for safe
+/// methods, `admin` otherwise.
+pub async fn require_key(State(stores): State<Stores>, req: Request, next: Next) -> Response {
+    let required = match *req.method() {
+        Method::GET | Method::HEAD => Role::Read,
+        _ => Role::Admin,
+    };
+    let bearer = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(bearer_token)
+        .map(str::to_string);
+    match authorize(&stores, bearer.as_deref(), required).await {
+        Ok(()) => next.run(req).await,
+        Err(e) => e.into_response(),
+    }
+}
+
+/// The credentials of a `Bearer` authorization; the scheme name is
+/// case-insensitive.
+fn bearer_token(authorization: &str) -> Option<&str> {
+    let (scheme, token) = authorization.trim().split_once(' ')?;
+    scheme
+        .eq_ignore_ascii_case("bearer")
+        .then(|| token.trim())
+        .filter(|t| !t.is_empty())
+}
+
+async fn authorize(stores: &Stores, bearer: Option<&str>, required: Role) -> Result<(), ApiError> {
+    let secret = bearer.ok_or_else(|| ApiError::Unauthorized("missing API key".to_string()))?;
+
+    let key = stores
+        .keys
+        .find_active(&hash(secret))
+        .await?
+        .ok_or_else(|| ApiError::Unauthorized("invalid or revoked API key".to_string()))?;
+
+    // Treat an unknown stored role as the least privileged one.
+    let role = key.role.parse().unwrap_or(Role::Read);
+    if role < required {
+        return Err(ApiError::Forbidden(format!(
+            "this operation requires the {} role",
+            required.name()
+        )));
+    }
+
+    // Record use at most once a minute rather than writing on every request.
+    let now = Utc::now();
+    let recent = key
+        .last_used_at
+        .as_deref()
+        .and_then(|ts| DateTime::parse_from_rfc3339(ts).ok())
+        .is_some_and(|ts| now - ts.with_timezone(&Utc) < TOUCH_INTERVAL);
+    if !recent {
+        if let Err(e) = stores.keys.touch(key.id, &now.to_rfc3339()).await {
+            tracing::warn!("Failed to record use of API key {}: {}", key.id, e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bearer_scheme_is_case_insensitive() {
+        assert_eq!(bearer_token("Bearer aiidx_1"), Some("aiidx_1"));
+        assert_eq!(bearer_token("bearer aiidx_1"), Some("aiidx_1"));
+        assert_eq!(bearer_token("BEARER  aiidx_1 "), Some("aiidx_1"));
+        assert_eq!(bearer_token("Basic dXNlcg=="), None);
+        assert_eq!(bearer_token("Bearer"), None);
+        assert_eq!(bearer_token("Bearer "), None);
+    }
+
+    #[tokio::test]
+    async fn use_is_recorded_at_most_once_a_minute() {
+        let stores = Stores::memory();
+        let (key, secret) = create_key(&stores, "ops", Role::Read).await.unwrap();
+        let last_used = || async {
+            let key = stores.keys.find_active(&hash(&secret)).await.unwrap();
+            key.unwrap().last_used_at
+        };
+
+        authorize(&stores, Some(&secret), Role::Read).await.unwrap();
+        let first = last_used().await;
+        assert!(first.is_some());
+        authorize(&stores, Some(&secret), Role::Read).await.unwrap();
+        assert_eq!(last_used().await, first);
+
+        let stale = (Utc::now() - TOUCH_INTERVAL * 2).to_rfc3339();
+        stores.keys.touch(key.id, &stale).await.unwrap();
+        authorize(&stores, Some(&secret), Role::Read).await.unwrap();
+        assert_ne!(last_used().await.as_deref(), Some(stale.as_str()));
+
+        let err = authorize(&stores, Some(&secret), Role::Admin).await;
+        assert!(matches!(err, Err(ApiError::Forbidden(_))));
+    }
+}
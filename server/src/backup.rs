@@ -0,0 +1,313 @@
+use crate::app_config::BackupsConfig;
+use crate::db::{self, Backend};
+use chrono::Utc;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use sqlx::AnyPool;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tokio::time::{self, Duration};
+
+const FILE_PREFIX: &str = "aiindex-";
+const FILE_SUFFIX: &str = ".db";
+
+/// Where backups go, how many to keep and how often to take them.
+#[derive(Debug, Clone)]
+pub struct BackupConfig {
+    pub dir: PathBuf,
+    pub keep: usize,
+    /// `None` disables scheduled backups; admin-triggered ones still work.
+    pub interval: Option<Duration>,
+}
+
+impl BackupConfig {
+    pub fn new(settings: &BackupsConfig) -> Self {
+        let hours = settings.interval_hours;
+        BackupConfig {
+            dir: settings.dir.clone(),
+            keep: settings.keep.max(1),
+            interval: (hours > 0).then(|| Duration::from_secs(hours * 60 * 60)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum BackupError {
+    /// Online backups rely on SQLite's `VACUUM INTO`.
+    Unsupported(Backend),
+    Io(std::io::Error),
+    Db(sqlx::Error),
+    /// The file is not a usable aiindex database.
+    Invalid(String),
+}
+
+impl std::fmt::Display for BackupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BackupError::Unsupported(b) => {
+                write!(
+                    f,
+                    "backups are not supported for {:?}; use the database's own tooling",
+                    b
+                )
+            }
+            BackupError::Io(e) => write!(f, "I/O error: {}", e),
+            BackupError::Db(e) => write!(f, "database error: {}", e),
+            BackupError::Invalid(msg) => write!(f, "invalid backup: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for BackupError {}
+
+impl From<std::io::Error> for BackupError {
+    fn from(e: std::io::Error) -> Self {
+        BackupError::Io(e)
+    }
+}
+
+impl From<sqlx::Error> for BackupError {
+    fn from(e: sqlx::Error) -> Self {
+        BackupError::Db(e)
+    }
+}
+
+pub use aiindex_client::types::BackupInfo;
+
+pub fn spawn(pool: AnyPool, cfg: BackupConfig) {
+    let Some(interval) = cfg.interval else {
+        tracing::info!("Scheduled backups disabled");
+        return;
+    };
+    if Backend::of(&pool) != Backend::Sqlite {
+        tracing::info!("Scheduled backups skipped: database is not SQLite");
+        return;
+    }
+    tokio::spawn(async move {
+        loop {
+            time::sleep(interval).await;
+            match create(&pool, &cfg).await {
+                Ok(b) => tracing::info!("Backup written to {} ({} bytes)", b.file, b.size_bytes),
+                Err(e) => tracing::error!("Scheduled backup failed: {}", e),
+            }
+        }
+    });
+}
+
+/// Take a consistent online snapshot with `VACUUM INTO`, then rotate old ones.
+pub async fn create(pool: &AnyPool, cfg: &BackupConfig) -> Result<BackupInfo, BackupError> {
+    let backend = Backend::of(pool);
+    if backend != Backend::Sqlite {
+        return Err(BackupError::Unsupported(backend));
+    }
+
+    tokio::fs::create_dir_all(&cfg.dir).await?;
+    let path = next_path(&cfg.dir).await?;
+    let path_str = path
+        .to_str()
+        .ok_or_else(|| BackupError::Invalid("backup path is not UTF-8".to_string()))?;
+
+    sqlx::query("VACUUM INTO $1")
+        .bind(path_str)
+        .execute(pool)
+        .await?;
+
+    rotate(&cfg.dir, cfg.keep).await?;
+
+    let size_bytes = tokio::fs::metadata(&path).await?.len();
+    Ok(BackupInfo {
+        file: path.display().to_string(),
+        size_bytes,
+    })
+}
+
+/// A backup path named for the current time, to the microsecond so names
+/// sort by age. `VACUUM INTO` refuses to overwrite, so a name already taken
+/// waits for the clock to move on.
+async fn next_path(dir: &Path) -> Result<PathBuf, BackupError> {
+    loop {
+        let name = format!(
+            "{}{}{}",
+            FILE_PREFIX,
+            Utc::now().format("%Y%m%dT%H%M%S%6fZ"),
+            FILE_SUFFIX
+        );
+        let path = dir.join(name);
+        if !tokio::fs::try_exists(&path).await? {
+            return Ok(path);
+        }
+        time::sleep(Duration::from_millis(1)).await;
+    }
+}
+
+/// Backups in the directory, newest first.
+pub async fn list(cfg: &BackupConfig) -> Result<Vec<BackupInfo>, BackupError> {
+    let mut out = Vec::new();
+    for path in backup_files(&cfg.dir).await?.into_iter().rev() {
+        let size_bytes = tokio::fs::metadata(&path).await?.len();
+        out.push(BackupInfo {
+            file: path.display().to_string(),
+            size_bytes,
+        });
+    }
+    Ok(out)
+}
+
+/// Replace the configured SQLite database with `file` once it validates.
+///
+/// Run with the server stopped. The current database is kept alongside as
+/// `<name>.pre-restore-<timestamp>`.
+pub async fn restore(file: &Path, db_url: &str) -> Result<PathBuf, BackupError> {
+    let backend = Backend::from_url(db_url)?;
+    if backend != Backend::Sqlite {
+        return Err(BackupError::Unsupported(backend));
+    }
+    let target = SqliteConnectOptions::from_str(db_url)?
+        .get_filename()
+        .to_path_buf();
+
+    validate(file).await?;
+
+    // Stage next to the target so the final rename stays on one filesystem.
+    let staged = with_suffix(&target, ".restore-tmp");
+    tokio::fs::copy(file, &staged).await?;
+
+    if tokio::fs::try_exists(&target).await? {
+        let kept = with_suffix(
+            &target,
+            &format!(".pre-restore-{}", Utc::now().format("%Y%m%dT%H%M%SZ")),
+        );
+        tokio::fs::rename(&target, &kept).await?;
+        tracing::info!("Previous database kept at {}", kept.display());
+    }
+    // A stale WAL from the old database would be replayed over the restore.
+    for sidecar in ["-wal", "-shm"] {
+        let _ = tokio::fs::remove_file(with_suffix(&target, sidecar)).await;
+    }
+    tokio::fs::rename(&staged, &target).await?;
+
+    Ok(target)
+}
+
+/// Check integrity, expected tables and schema version of a backup file.
+async fn validate(file: &Path) -> Result<(), BackupError> {
+    if !tokio::fs::try_exists(file).await? {
+        return Err(BackupError::Invalid(format!(
+            "{} not found",
+            file.display()
+        )));
+    }
+
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect_with(SqliteConnectOptions::new().filename(file).read_only(true))
+        .await?;
+
+    let (integrity,) = sqlx::query_as::<_, (String,)>("PRAGMA integrity_check")
+        .fetch_one(&pool)
+        .await?;
+    if integrity != "ok" {
+        return Err(BackupError::Invalid(format!(
+            "integrity check: {}",
+            integrity
+        )));
+    }
+
+    for table in [
+        "_sqlx_migrations",
+        "prices",
+        "index_snapshots",
+        "base_prices",
+    ] {
+        let (n,) = sqlx::query_as::<_, (i64,)>(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = $1",
+        )
+        .bind(table)
+        .fetch_one(&pool)
+        .await?;
+        if n == 0 {
+            return Err(BackupError::Invalid(format!("missing table {}", table)));
+        }
+    }
+
+    let (version,) = sqlx::query_as::<_, (Option<i64>,)>(
+        "SELECT MAX(version) FROM _sqlx_migrations WHERE success = TRUE",
+    )
+    .fetch_one(&pool)
+    .await?;
+    let latest = db::latest_version(Backend::Sqlite);
+    match version {
+        Some(v) if v > latest => Err(BackupError::Invalid(format!(
+            "schema version {} is newer than this build supports ({})",
+            v, latest
+        ))),
+        None => Err(BackupError::Invalid("no applied migrations".to_string())),
+        Some(_) => Ok(()),
+    }
+}
+
+/// `path` with `suffix` appended to its file name.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut p = path.as_os_str().to_owned();
+    p.push(suffix);
+    PathBuf::from(p)
+}
+
+/// Delete all but the newest `keep` backups.
+async fn rotate(dir: &Path, keep: usize) -> Result<(), BackupError> {
+    let files = backup_files(dir).await?;
+    let excess = files.len().saturating_sub(keep);
+    for old in &files[..excess] {
+        tokio::fs::remove_file(old).await?;
+        tracing::info!("Rotated out backup {}", old.display());
+    }
+    Ok(())
+}
+
+/// Backup files in `dir`, oldest first (names sort by timestamp).
+async fn backup_files(dir: &Path) -> Result<Vec<PathBuf>, BackupError> {
+    let mut files = Vec::new();
+    let mut entries = match tokio::fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(files),
+        Err(e) => return Err(e.into()),
+    };
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with(FILE_PREFIX) && name.ends_with(FILE_SUFFIX) {
+            files.push(entry.path());
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::ScratchDb;
+
+    #[tokio::test]
+    async fn backups_in_quick_succession_do_not_collide() {
+        let db = ScratchDb::sqlite().await;
+        let dir = std::env::temp_dir().join(format!("aiindex-backups-{}", uuid::Uuid::new_v4()));
+        let cfg = BackupConfig {
+            dir: dir.clone(),
+            keep: 5,
+            interval: None,
+        };
+
+        let first = create(&db.pool, &cfg).await.unwrap();
+        let second = create(&db.pool, &cfg).await.unwrap();
+        let third = create(&db.pool, &cfg).await.unwrap();
+        let listed: Vec<String> = list(&cfg)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|b| b.file)
+            .collect();
+        assert_eq!(listed, [third.file, second.file, first.file]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
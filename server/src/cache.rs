@@ -0,0 +1,139 @@
+//! In-memory view of the latest quotes, profiles, weights and snapshot.
+//!
+//! The fetcher rebuilds it after each run and swaps it in whole, so request
+//! handlers read a consistent state without touching the database.
+
+use crate::config::StocksConfig;
+use crate::index;
+use crate::models::{IndexSnapshot, PriceRecord, StockProfile};
+use crate::store::{StoreResult, Stores};
+use arc_swap::ArcSwap;
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Arc;
+
+#[derive(Debug, Default)]
+pub struct Latest {
+    pub snapshot: Option<IndexSnapshot>,
+    /// Most recent price row per symbol.
+    pub prices: HashMap<String, PriceRecord>,
+    pub profiles: HashMap<String, StockProfile>,
+    /// Blended weight per index symbol.
+    pub weights: HashMap<String, f64>,
+    /// Validator for responses built from this state; changes whenever the
+    /// snapshot, a quote, a profile or the stored history does. Empty before
+    /// the first load.
+    pub etag: String,
+    /// Newest snapshot or quote timestamp.
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+impl Latest {
+    /// Read everything in four queries and derive the weights.
+    async fn load(stores: &Stores, config: &StocksConfig, seed: u64) -> StoreResult<Self> {
+        let snapshot = stores.snapshots.latest().await?;
+        let prices: HashMap<String, PriceRecord> = stores
+            .prices
+            .latest_all()
+            .await?
+            .into_iter()
+            .map(|r| (r.symbol.clone(), r))
+            .collect();
+        let profiles = stores.profiles.all().await?.into_iter().collect();
+        let weights = index::weights(config, &prices);
+        let version = stores.snapshots.data_version().await?;
+        let (etag, last_modified) = validators(
+            (seed ^ config.fingerprint, version),
+            &snapshot,
+            &prices,
+            &profiles,
+        );
+        Ok(Latest {
+            snapshot,
+            prices,
+            profiles,
+            weights,
+            etag,
+            last_modified,
+        })
+    }
+
+    pub fn profile(&self, symbol: &str) -> StockProfile {
+        self.profiles.get(symbol).cloned().unwrap_or_default()
+    }
+}
+
+/// Fingerprint the state and find its newest timestamp. `seed` covers what
+/// else the responses depend on: the configuration and the version of the
+/// stored history behind the history routes.
+fn validators(
+    seed: impl Hash,
+    snapshot: &Option<IndexSnapshot>,
+    prices: &HashMap<String, PriceRecord>,
+    profiles: &HashMap<String, StockProfile>,
+) -> (String, Option<DateTime<Utc>>) {
+    let mut hasher = DefaultHasher::new();
+    // A new release may change response shapes for the same data.
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    seed.hash(&mut hasher);
+    let mut timestamps: Vec<&str> = Vec::new();
+
+    if let Some(s) = snapshot {
+        (&s.timestamp, s.value.to_bits()).hash(&mut hasher);
+        timestamps.push(&s.timestamp);
+    }
+    let prices: BTreeMap<_, _> = prices.iter().collect();
+    for (symbol, r) in prices {
+        (symbol, &r.timestamp, r.price.to_bits()).hash(&mut hasher);
+        r.market_cap.map(f64::to_bits).hash(&mut hasher);
+        timestamps.push(&r.timestamp);
+    }
+    let profiles: BTreeMap<_, _> = profiles.iter().collect();
+    for (symbol, p) in profiles {
+        symbol.hash(&mut hasher);
+        serde_json::to_string(p)
+            .unwrap_or_default()
+            .hash(&mut hasher);
+    }
+
+    let last_modified = timestamps
+        .into_iter()
+        .filter_map(|ts| DateTime::parse_from_rfc3339(ts).ok())
+        .map(|ts| ts.with_timezone(&Utc))
+        .max();
+    (format!("\"{:016x}\"", hasher.finish()), last_modified)
+}
+
+/// Cheaply cloneable handle to the current [`Latest`].
+#[derive(Clone, Default)]
+pub struct Cache {
+    latest: Arc<ArcSwap<Latest>>,
+    /// Mixed into every ETag; see [`Cache::new`].
+    seed: u64,
+}
+
+impl Cache {
+    /// A cache whose ETags also change with `settings`, for responses that
+    /// report the runtime settings.
+    pub fn new(settings: &impl Hash) -> Self {
+        let mut hasher = DefaultHasher::new();
+        settings.hash(&mut hasher);
+        Cache {
+            latest: Arc::default(),
+            seed: hasher.finish(),
+        }
+    }
+
+    pub fn load(&self) -> Arc<Latest> {
+        self.latest.load_full()
+    }
+
+    /// Rebuild from the stores and replace the current state. On error the
+    /// previous state is kept.
+    pub async fn refresh(&self, stores: &Stores, config: &StocksConfig) -> StoreResult<()> {
+        let latest = Latest::load(stores, config, self.seed).await?;
+        self.latest.store(Arc::new(latest));
+        Ok(())
+    }
+}
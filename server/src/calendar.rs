@@ -0,0 +1,220 @@
+use crate::config::Exchange;
+use chrono::{DateTime, Datelike, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+
+/// NYSE full-day closures for the built-in "US" exchange, as published by
+/// the exchange. Early closes are not modelled. Extend each year.
+const NYSE_HOLIDAYS: [(i32, u32, u32); 31] = [
+    // 2025
+    (2025, 1, 1),
+    (2025, 1, 9),
+    (2025, 1, 20),
+    (2025, 2, 17),
+    (2025, 4, 18),
+    (2025, 5, 26),
+    (2025, 6, 19),
+    (2025, 7, 4),
+    (2025, 9, 1),
+    (2025, 11, 27),
+    (2025, 12, 25),
+    // 2026
+    (2026, 1, 1),
+    (2026, 1, 19),
+    (2026, 2, 16),
+    (2026, 4, 3),
+    (2026, 5, 25),
+    (2026, 6, 19),
+    (2026, 7, 3),
+    (2026, 9, 7),
+    (2026, 11, 26),
+    (2026, 12, 25),
+    // 2027
+    (2027, 1, 1),
+    (2027, 1, 18),
+    (2027, 2, 15),
+    (2027, 3, 26),
+    (2027, 5, 31),
+    (2027, 6, 18),
+    (2027, 7, 5),
+    (2027, 9, 6),
+    (2027, 11, 25),
+    (2027, 12, 24),
+];
+
+impl Exchange {
+    /// NYSE/Nasdaq regular session, used when stocks.toml defines no US exchange.
+    pub fn us() -> Self {
+        Exchange {
+            timezone: chrono_tz::America::New_York,
+            open: NaiveTime::from_hms_opt(9, 30, 0).unwrap(),
+            close: NaiveTime::from_hms_opt(16, 0, 0).unwrap(),
+            holidays: NYSE_HOLIDAYS
+                .iter()
+                .filter_map(|&(y, m, d)| NaiveDate::from_ymd_opt(y, m, d))
+                .collect(),
+            symbols: Vec::new(),
+        }
+    }
+
+    /// Whether the exchange holds a regular session on this local date.
+    pub fn is_session_day(&self, date: NaiveDate) -> bool {
+        !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) && !self.holidays.contains(&date)
+    }
+
+    /// The session an instant's price belongs to. Before the open, and on
+    /// closed days, the last print is the previous session's close.
+    pub fn trading_date(&self, ts: DateTime<Utc>) -> NaiveDate {
+        let local = ts.with_timezone(&self.timezone);
+        let date = local.date_naive();
+        if self.is_session_day(date) && local.time() >= self.open {
+            date
+        } else {
+            self.previous_session(date)
+        }
+    }
+
+    /// The last session day strictly before `date`.
+    pub fn previous_session(&self, date: NaiveDate) -> NaiveDate {
+        let mut d = date.pred_opt().unwrap_or(date);
+        // Bounded so a misconfigured holiday list cannot spin forever.
+        for _ in 0..14 {
+            if self.is_session_day(d) {
+                break;
+            }
+            d = d.pred_opt().unwrap_or(d);
+        }
+        d
+    }
+
+    /// The first session day strictly after `date`.
+    pub fn next_session(&self, date: NaiveDate) -> NaiveDate {
+        let mut d = date.succ_opt().unwrap_or(date);
+        for _ in 0..14 {
+            if self.is_session_day(d) {
+                break;
+            }
+            d = d.succ_opt().unwrap_or(d);
+        }
+        d
+    }
+
+    /// The UTC instant of the session open on a local trading date.
+    pub fn session_open(&self, date: NaiveDate) -> DateTime<Utc> {
+        self.local_instant(date, self.open)
+    }
+
+    /// The UTC instant of the session close on a local trading date.
+    pub fn session_close(&self, date: NaiveDate) -> DateTime<Utc> {
+        self.local_instant(date, self.close)
+    }
+
+    fn local_instant(&self, date: NaiveDate, time: NaiveTime) -> DateTime<Utc> {
+        let local = date.and_time(time);
+        self.timezone
+            .from_local_datetime(&local)
+            .earliest()
+            .map(|dt| dt.with_timezone(&Utc))
+            // Time falls in a DST gap; treat the wall-clock time as UTC-offset-free.
+            .unwrap_or_else(|| local.and_utc())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nyse() -> Exchange {
+        Exchange {
+            holidays: vec![NaiveDate::from_ymd_opt(2024, 7, 4).unwrap()],
+            ..Exchange::us()
+        }
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn session_close_follows_dst() {
+        let x = nyse();
+        // Clocks went forward on Sunday 2024-03-10.
+        assert_eq!(
+            x.session_close(date(2024, 3, 8)),
+            utc("2024-03-08T21:00:00Z")
+        );
+        assert_eq!(
+            x.session_close(date(2024, 3, 11)),
+            utc("2024-03-11T20:00:00Z")
+        );
+        assert_eq!(
+            x.session_open(date(2024, 3, 11)),
+            utc("2024-03-11T13:30:00Z")
+        );
+    }
+
+    #[test]
+    fn trading_date_across_dst() {
+        let x = nyse();
+        // 09:00 EDT is before the open, so it belongs to Friday's session.
+        assert_eq!(
+            x.trading_date(utc("2024-03-11T13:00:00Z")),
+            date(2024, 3, 8)
+        );
+        // 09:30 EDT is the open.
+        assert_eq!(
+            x.trading_date(utc("2024-03-11T13:30:00Z")),
+            date(2024, 3, 11)
+        );
+        // 09:00 EST on the Friday before was also pre-open.
+        assert_eq!(
+            x.trading_date(utc("2024-03-08T14:00:00Z")),
+            date(2024, 3, 7)
+        );
+    }
+
+    #[test]
+    fn trading_date_after_the_close_stays_on_the_session() {
+        let x = nyse();
+        assert_eq!(
+            x.trading_date(utc("2024-03-12T23:00:00Z")),
+            date(2024, 3, 12)
+        );
+    }
+
+    #[test]
+    fn built_in_us_calendar_knows_thanksgiving() {
+        let x = Exchange::us();
+        assert!(!x.is_session_day(date(2026, 11, 26)));
+        // Thursday afternoon's prints belong to Wednesday's session.
+        assert_eq!(
+            x.trading_date(utc("2026-11-26T18:00:00Z")),
+            date(2026, 11, 25)
+        );
+        assert_eq!(x.previous_session(date(2026, 11, 27)), date(2026, 11, 25));
+
+        // The shipped stocks.toml lists the same closures.
+        let config = crate::config::StocksConfig::load(None).unwrap();
+        assert_eq!(config.exchanges["US"].holidays, x.holidays);
+    }
+
+    #[test]
+    fn holidays_and_weekends_roll_back() {
+        let x = nyse();
+        assert!(!x.is_session_day(date(2024, 7, 4)));
+        assert_eq!(
+            x.trading_date(utc("2024-07-04T18:00:00Z")),
+            date(2024, 7, 3)
+        );
+        assert_eq!(x.previous_session(date(2024, 7, 5)), date(2024, 7, 3));
+        assert_eq!(
+            x.trading_date(utc("2024-07-06T18:00:00Z")),
+            date(2024, 7, 5)
+        );
+        assert_eq!(x.previous_session(date(2024, 7, 8)), date(2024, 7, 5));
+        assert_eq!(x.next_session(date(2024, 7, 3)), date(2024, 7, 5));
+        assert_eq!(x.next_session(date(2024, 7, 5)), date(2024, 7, 8));
+    }
+}
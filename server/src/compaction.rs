@@ -0,0 +1,500 @@
+use crate::app_config::CompactionConfig;
+use crate::cache::Cache;
+use crate::config::{Exchange, SharedConfig, StocksConfig};
+use crate::metrics::COMPACTION;
+use crate::store::Stores;
+use chrono::{DateTime, Duration as ChronoDuration, NaiveDate, Utc};
+use sqlx::{AnyPool, FromRow};
+use std::sync::atomic::Ordering;
+use tokio::time::{self, Duration, Instant};
+
+/// How long intraday rows are kept before being rolled into daily bars.
+/// A value of 0 keeps intraday rows forever.
+#[derive(Debug, Clone, Copy)]
+pub struct RetentionPolicy {
+    pub price_days: i64,
+    pub snapshot_days: i64,
+    pub interval: Duration,
+}
+
+impl RetentionPolicy {
+    pub fn new(settings: &CompactionConfig) -> Self {
+        RetentionPolicy {
+            price_days: settings.price_retention_days,
+            snapshot_days: settings.snapshot_retention_days,
+            interval: Duration::from_secs(settings.interval_hours.max(1) * 60 * 60),
+        }
+    }
+}
+
+/// Compact on a schedule. Passes that rewrite rows bump the data version and
+/// refresh `cache`, so cached history responses stop validating.
+pub fn spawn(
+    pool: AnyPool,
+    stores: Stores,
+    cache: Cache,
+    config: SharedConfig,
+    settings: &CompactionConfig,
+) {
+    if !settings.enabled {
+        tracing::info!("Compaction disabled");
+        return;
+    }
+    let policy = RetentionPolicy::new(settings);
+    tracing::info!(
+        "Compaction: prices after {} days, snapshots after {} days, every {:?}",
+        policy.price_days,
+        policy.snapshot_days,
+        policy.interval
+    );
+    tokio::spawn(async move {
+        loop {
+            let current = config.load();
+            if run(&pool, &current, policy).await {
+                if let Err(e) = stores.snapshots.bump_data_version().await {
+                    tracing::error!("Failed to bump the data version: {}", e);
+                }
+                if let Err(e) = cache.refresh(&stores, &current).await {
+                    tracing::error!("Failed to refresh latest-state cache: {}", e);
+                }
+            }
+            time::sleep(policy.interval).await;
+        }
+    });
+}
+
+/// One compaction pass over every symbol and the index snapshots. Returns
+/// whether any rows were rewritten.
+pub async fn run(pool: &AnyPool, config: &StocksConfig, policy: RetentionPolicy) -> bool {
+    let started = Instant::now();
+    let mut stats = Stats::default();
+    let mut failed = false;
+
+    if policy.price_days > 0 {
+        let cutoff = Utc::now() - ChronoDuration::days(policy.price_days);
+        for symbol in config.all_symbols() {
+            let exchange = config.exchange_for(&symbol);
+            if let Err(e) = compact_prices(pool, &symbol, exchange, cutoff, &mut stats).await {
+                tracing::error!("{}: price compaction failed: {}", symbol, e);
+                failed = true;
+            }
+        }
+    }
+
+    if policy.snapshot_days > 0 {
+        let cutoff = Utc::now() - ChronoDuration::days(policy.snapshot_days);
+        let exchange = config.reference_exchange();
+        if let Err(e) = compact_snapshots(pool, exchange, cutoff, &mut stats).await {
+            tracing::error!("Snapshot compaction failed: {}", e);
+            failed = true;
+        }
+    }
+
+    COMPACTION.runs.fetch_add(1, Ordering::Relaxed);
+    if failed {
+        COMPACTION.failures.fetch_add(1, Ordering::Relaxed);
+    }
+    COMPACTION
+        .rows_removed
+        .fetch_add(stats.rows_removed, Ordering::Relaxed);
+    COMPACTION
+        .bars_written
+        .fetch_add(stats.bars_written, Ordering::Relaxed);
+    COMPACTION
+        .last_run_unix
+        .store(Utc::now().timestamp().max(0) as u64, Ordering::Relaxed);
+    COMPACTION
+        .last_duration_ms
+        .store(started.elapsed().as_millis() as u64, Ordering::Relaxed);
+
+    tracing::info!(
+        "Compaction complete: {} intraday rows folded into {} daily bars",
+        stats.rows_removed,
+        stats.bars_written
+    );
+    stats.rows_removed > 0
+}
+
+#[derive(Default)]
+struct Stats {
+    rows_removed: u64,
+    bars_written: u64,
+}
+
+#[derive(FromRow)]
+struct PriceRow {
+    id: i64,
+    price: f64,
+    open: Option<f64>,
+    high: Option<f64>,
+    low: Option<f64>,
+    volume: Option<f64>,
+    change: Option<f64>,
+    change_pct: Option<f64>,
+    market_cap: Option<f64>,
+    timestamp: String,
+}
+
+#[derive(FromRow)]
+struct SnapshotRow {
+    id: i64,
+    value: f64,
+    open: Option<f64>,
+    high: Option<f64>,
+    low: Option<f64>,
+    timestamp: String,
+}
+
+async fn compact_prices(
+    pool: &AnyPool,
+    symbol: &str,
+    exchange: &Exchange,
+    cutoff: DateTime<Utc>,
+    stats: &mut Stats,
+) -> Result<(), sqlx::Error> {
+    let rows = sqlx::query_as::<_, PriceRow>(
+        "SELECT id, price, open, high, low, volume, change, change_pct, market_cap, timestamp
+         FROM prices WHERE symbol = $1 AND timestamp < $2 ORDER BY timestamp",
+    )
+    .bind(symbol)
+    .bind(cutoff.to_rfc3339())
+    .fetch_all(pool)
+    .await?;
+
+    for (date, group) in complete_sessions(rows, |r| &r.timestamp, exchange, cutoff) {
+        let close_ts = exchange.session_close(date).to_rfc3339();
+        if group.len() == 1 && group[0].timestamp == close_ts {
+            continue;
+        }
+
+        let first = &group[0];
+        let last = &group[group.len() - 1];
+        let high = group
+            .iter()
+            .map(|r| r.high.unwrap_or(r.price))
+            .fold(f64::MIN, f64::max);
+        let low = group
+            .iter()
+            .map(|r| r.low.unwrap_or(r.price))
+            .fold(f64::MAX, f64::min);
+        let market_cap = group.iter().rev().find_map(|r| r.market_cap);
+        let volume = group.iter().filter_map(|r| r.volume).reduce(|a, b| a + b);
+
+        let mut tx = pool.begin().await?;
+        for row in &group {
+            sqlx::query("DELETE FROM prices WHERE id = $1")
+                .bind(row.id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        sqlx::query(
+            "INSERT INTO prices (symbol, price, open, high, low, volume, change, change_pct, market_cap, timestamp)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+             ON CONFLICT(symbol, timestamp) DO UPDATE SET
+               price = excluded.price,
+               open = excluded.open,
+               high = excluded.high,
+               low = excluded.low,
+               volume = excluded.volume,
+               change = excluded.change,
+               change_pct = excluded.change_pct,
+               market_cap = excluded.market_cap",
+        )
+        // NULL f64s confuse cached Postgres statements under sqlx::Any.
+        .persistent(false)
+        .bind(symbol)
+        .bind(last.price)
+        .bind(first.open.unwrap_or(first.price))
+        .bind(high)
+        .bind(low)
+        .bind(volume)
+        .bind(last.change)
+        .bind(last.change_pct)
+        .bind(market_cap)
+        .bind(&close_ts)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        stats.rows_removed += group.len() as u64;
+        stats.bars_written += 1;
+    }
+
+    Ok(())
+}
+
+async fn compact_snapshots(
+    pool: &AnyPool,
+    exchange: &Exchange,
+    cutoff: DateTime<Utc>,
+    stats: &mut Stats,
+) -> Result<(), sqlx::Error> {
+    let rows = sqlx::query_as::<_, SnapshotRow>(
+        "SELECT id, value, open, high, low, timestamp
+         FROM index_snapshots WHERE timestamp < $1 ORDER BY timestamp",
+    )
+    .bind(cutoff.to_rfc3339())
+    .fetch_all(pool)
+    .await?;
+
+    // Session-over-session change for each bar, chained through the run.
+    let mut prev_close: Option<f64> = None;
+
+    for (date, group) in complete_sessions(rows, |r| &r.timestamp, exchange, cutoff) {
+        let first = &group[0];
+        let last = &group[group.len() - 1];
+        let close_ts = exchange.session_close(date).to_rfc3339();
+        let prev = prev_close.replace(last.value);
+        if group.len() == 1 && first.timestamp == close_ts {
+            continue;
+        }
+
+        let high = group
+            .iter()
+            .map(|r| r.high.unwrap_or(r.value))
+            .fold(f64::MIN, f64::max);
+        let low = group
+            .iter()
+            .map(|r| r.low.unwrap_or(r.value))
+            .fold(f64::MAX, f64::min);
+        let (daily_change, daily_change_pct) = match prev {
+            Some(p) if p > 0.0 => (Some(last.value - p), Some((last.value - p) / p * 100.0)),
+            _ => (None, None),
+        };
+
+        let mut tx = pool.begin().await?;
+        for row in &group {
+            sqlx::query("DELETE FROM index_snapshots WHERE id = $1")
+                .bind(row.id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        sqlx::query(
+            "INSERT INTO index_snapshots (value, open, high, low, daily_change, daily_change_pct, timestamp)
+             VALUES ($1, $2, $3, $4, $5, $6, $7)
+             ON CONFLICT(timestamp) DO UPDATE SET
+               value = excluded.value,
+               open = excluded.open,
+               high = excluded.high,
+               low = excluded.low,
+               daily_change = excluded.daily_change,
+               daily_change_pct = excluded.daily_change_pct",
+        )
+        // NULL f64s confuse cached Postgres statements under sqlx::Any.
+        .persistent(false)
+        .bind(last.value)
+        .bind(first.open.unwrap_or(first.value))
+        .bind(high)
+        .bind(low)
+        .bind(daily_change)
+        .bind(daily_change_pct)
+        .bind(&close_ts)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        stats.rows_removed += group.len() as u64;
+        stats.bars_written += 1;
+    }
+
+    Ok(())
+}
+
+/// Split timestamp-ordered rows into runs that share a trading session,
+/// keeping only sessions that ended before `cutoff`. A session's rows run
+/// until the next session opens, so one still in progress at the cutoff
+/// may have rows after it; folding only its earlier rows would stamp a
+/// partial bar at the close, ahead of the rows that follow.
+fn complete_sessions<T>(
+    rows: Vec<T>,
+    timestamp: impl Fn(&T) -> &str,
+    exchange: &Exchange,
+    cutoff: DateTime<Utc>,
+) -> Vec<(NaiveDate, Vec<T>)> {
+    let current = exchange.trading_date(cutoff);
+    let mut out: Vec<(NaiveDate, Vec<T>)> = Vec::new();
+    for row in rows {
+        let Ok(ts) = DateTime::parse_from_rfc3339(timestamp(&row)) else {
+            continue;
+        };
+        let date = exchange.trading_date(ts.with_timezone(&Utc));
+        match out.last_mut() {
+            Some((d, group)) if *d == date => group.push(row),
+            _ => out.push((date, vec![row])),
+        }
+    }
+    out.retain(|(date, _)| *date < current);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::ScratchDb;
+    use crate::models::{IndexSnapshot, PriceRecord};
+    use crate::store::Stores;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    async fn seed_prices(stores: &Stores, points: &[(&str, f64)]) {
+        for (ts, price) in points {
+            stores
+                .prices
+                .upsert(&PriceRecord {
+                    symbol: "NVDA".to_string(),
+                    price: *price,
+                    change: Some(price - 100.0),
+                    change_pct: None,
+                    market_cap: None,
+                    timestamp: utc(ts).to_rfc3339(),
+                })
+                .await
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn prices_are_compacted_once_the_session_is_over() {
+        let db = ScratchDb::sqlite().await;
+        let stores = Stores::sql(db.pool.clone());
+        let exchange = Exchange::us();
+        // Monday 2024-03-11, 09:30–16:00 EDT (13:30–20:00 UTC).
+        seed_prices(
+            &stores,
+            &[
+                ("2024-03-11T14:00:00Z", 101.0),
+                ("2024-03-11T15:00:00Z", 107.0),
+                ("2024-03-11T17:00:00Z", 99.0),
+                ("2024-03-11T19:45:00Z", 104.0),
+            ],
+        )
+        .await;
+
+        // Mid-session: nothing is folded, even rows before the cutoff.
+        let mut stats = Stats::default();
+        compact_prices(
+            &db.pool,
+            "NVDA",
+            &exchange,
+            utc("2024-03-11T16:00:00Z"),
+            &mut stats,
+        )
+        .await
+        .unwrap();
+        assert_eq!(stats.bars_written, 0);
+        assert_eq!(stores.prices.series("NVDA").await.unwrap().len(), 4);
+
+        // A quote after the close still belongs to Monday's session.
+        seed_prices(&stores, &[("2024-03-11T20:05:00Z", 105.0)]).await;
+        // After the close but before Tuesday's open: still Monday's session.
+        compact_prices(
+            &db.pool,
+            "NVDA",
+            &exchange,
+            utc("2024-03-12T10:00:00Z"),
+            &mut stats,
+        )
+        .await
+        .unwrap();
+        assert_eq!(stats.bars_written, 0);
+
+        // Once Tuesday opens, Monday folds into one bar at its close.
+        compact_prices(
+            &db.pool,
+            "NVDA",
+            &exchange,
+            utc("2024-03-12T14:00:00Z"),
+            &mut stats,
+        )
+        .await
+        .unwrap();
+        assert_eq!((stats.rows_removed, stats.bars_written), (5, 1));
+        let bars = stores
+            .prices
+            .ohlc(
+                "NVDA",
+                "2024-03-11T00:00:00+00:00",
+                "2024-03-13T00:00:00+00:00",
+            )
+            .await
+            .unwrap();
+        assert_eq!(bars.len(), 1);
+        let bar = &bars[0];
+        assert_eq!(
+            bar.timestamp,
+            exchange
+                .session_close(NaiveDate::from_ymd_opt(2024, 3, 11).unwrap())
+                .to_rfc3339()
+        );
+        assert_eq!(
+            (bar.open, bar.high, bar.low, bar.close),
+            (Some(101.0), Some(107.0), Some(99.0), 105.0)
+        );
+        let latest = stores.prices.latest("NVDA").await.unwrap().unwrap();
+        assert_eq!(latest.change, Some(5.0));
+
+        // A later pass leaves the bar alone.
+        compact_prices(
+            &db.pool,
+            "NVDA",
+            &exchange,
+            utc("2024-03-20T14:00:00Z"),
+            &mut stats,
+        )
+        .await
+        .unwrap();
+        assert_eq!(stats.bars_written, 1);
+    }
+
+    #[tokio::test]
+    async fn snapshots_are_compacted_once_the_session_is_over() {
+        let db = ScratchDb::sqlite().await;
+        let stores = Stores::sql(db.pool.clone());
+        let exchange = Exchange::us();
+        for (ts, value) in [
+            ("2024-03-08T20:00:00Z", 1000.0),
+            ("2024-03-11T14:00:00Z", 1005.0),
+            ("2024-03-11T16:00:00Z", 1020.0),
+            ("2024-03-11T19:00:00Z", 990.0),
+            ("2024-03-11T19:55:00Z", 1010.0),
+        ] {
+            stores
+                .snapshots
+                .upsert(&IndexSnapshot {
+                    value,
+                    daily_change: None,
+                    daily_change_pct: None,
+                    timestamp: utc(ts).to_rfc3339(),
+                })
+                .await
+                .unwrap();
+        }
+
+        let mut stats = Stats::default();
+        compact_snapshots(&db.pool, &exchange, utc("2024-03-11T17:00:00Z"), &mut stats)
+            .await
+            .unwrap();
+        // Only Friday, which ended before the cutoff, moves to its close.
+        assert_eq!(stats.bars_written, 1);
+        assert_eq!(stores.snapshots.history(10).await.unwrap().len(), 5);
+
+        compact_snapshots(&db.pool, &exchange, utc("2024-03-12T14:00:00Z"), &mut stats)
+            .await
+            .unwrap();
+        assert_eq!(stats.bars_written, 2);
+        let bars = stores
+            .snapshots
+            .ohlc("2024-03-11T00:00:00+00:00", "2024-03-12T00:00:00+00:00")
+            .await
+            .unwrap();
+        assert_eq!(bars.len(), 1);
+        assert_eq!(
+            (bars[0].open, bars[0].high, bars[0].low, bars[0].close),
+            (Some(1005.0), Some(1020.0), Some(990.0), 1010.0)
+        );
+        let latest = stores.snapshots.latest().await.unwrap().unwrap();
+        assert_eq!(latest.daily_change, Some(10.0));
+    }
+}
@@ -1,25 +1,54 @@
+use crate::validate::{self, Diagnostic};
+use arc_swap::ArcSwap;
+use chrono::{NaiveDate, NaiveTime};
+use chrono_tz::Tz;
+use indexmap::IndexMap;
 use serde::Deserialize;
-use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::Path;
+use std::sync::Arc;
 
 const DEFAULT_TOML: &str = include_str!("../../stocks.toml");
 
+/// Exchange used for symbols not listed under any `[exchanges.*]` table.
+const DEFAULT_EXCHANGE: &str = "US";
+
 #[derive(Debug, Deserialize)]
 pub struct StocksConfig {
     pub settings: Settings,
-    pub sectors: HashMap<String, Sector>,
+    /// In file order, which is the order the API lists them in.
+    pub sectors: IndexMap<String, Sector>,
     pub benchmarks: Benchmarks,
+    /// In file order, so a symbol listed twice resolves to its first listing.
+    #[serde(default)]
+    pub exchanges: IndexMap<String, Exchange>,
+    /// Hash of the source text, so responses derived from the config can be
+    /// revalidated after a reload.
+    #[serde(skip)]
+    pub fingerprint: u64,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Settings {
     pub base_value: f64,
     pub market_cap_weight_pct: u32,
+    /// Exchange whose sessions define the index's trading dates.
+    #[serde(default = "default_exchange")]
+    pub reference_exchange: String,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct Sector {
     pub label: String,
     pub symbols: Vec<String>,
+    /// CSS hex color, e.g. `#00d4ff`.
+    #[serde(default)]
+    pub color: Option<String>,
+    /// Compact label for narrow layouts.
+    #[serde(default)]
+    pub short_label: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -27,17 +56,93 @@ pub struct Benchmarks {
     pub symbols: Vec<String>,
 }
 
+/// Trading venue with its local timezone and regular session hours.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Exchange {
+    pub timezone: Tz,
+    pub open: NaiveTime,
+    pub close: NaiveTime,
+    /// Full-day closures, in exchange-local dates.
+    #[serde(default)]
+    pub holidays: Vec<NaiveDate>,
+    /// Symbols listed on this exchange.
+    #[serde(default)]
+    pub symbols: Vec<String>,
+}
+
+fn default_exchange() -> String {
+    DEFAULT_EXCHANGE.to_string()
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(String, std::io::Error),
+    /// Validation errors, by line, in the named file.
+    Invalid(String, Vec<Diagnostic>),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(origin, e) => write!(f, "cannot read {}: {}", origin, e),
+            ConfigError::Invalid(origin, errors) => {
+                write!(f, "{} is invalid", origin)?;
+                for e in errors {
+                    write!(f, "\n  {}", e)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
 impl StocksConfig {
-    pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
-        let toml_str = if let Ok(path) = std::env::var("AIINDEX_STOCKS_PATH") {
-            std::fs::read_to_string(&path)?
-        } else {
-            DEFAULT_TOML.to_string()
-        };
-        let config: StocksConfig = toml::from_str(&toml_str)?;
+    /// Load from `path`, or the built-in `stocks.toml`. Warnings are
+    /// logged; any validation error fails the load.
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let (origin, text) = Self::source(path)?;
+        Self::parse(origin, &text)
+    }
+
+    /// Validate and parse config text. `origin` names it in errors.
+    pub fn parse(origin: String, text: &str) -> Result<Self, ConfigError> {
+        let report = validate::check(text);
+        for warning in report.warnings() {
+            tracing::warn!("{}: {}", origin, warning);
+        }
+        if report.has_errors() {
+            return Err(ConfigError::Invalid(
+                origin,
+                report.errors().cloned().collect(),
+            ));
+        }
+        let mut config: StocksConfig = toml::from_str(text)
+            .map_err(|e| ConfigError::Invalid(origin, vec![validate::parse_error(text, &e)]))?;
+        config
+            .exchanges
+            .entry(DEFAULT_EXCHANGE.to_string())
+            .or_insert_with(Exchange::us);
+        let mut hasher = DefaultHasher::new();
+        text.hash(&mut hasher);
+        config.fingerprint = hasher.finish();
         Ok(config)
     }
 
+    /// A name for the config's origin, and its text.
+    pub fn source(path: Option<&Path>) -> Result<(String, String), ConfigError> {
+        match path {
+            Some(path) => {
+                let origin = path.display().to_string();
+                std::fs::read_to_string(path)
+                    .map(|text| (origin.clone(), text))
+                    .map_err(|e| ConfigError::Io(origin, e))
+            }
+            None => Ok(("built-in stocks.toml".to_string(), DEFAULT_TOML.to_string())),
+        }
+    }
+
     /// All index symbols (excludes benchmarks).
     pub fn index_symbols(&self) -> Vec<String> {
         self.sectors
@@ -53,8 +158,63 @@ impl StocksConfig {
         syms
     }
 
+    /// The sector key a symbol belongs to, or `benchmarks` for benchmarks.
+    pub fn sector_of(&self, symbol: &str) -> Option<&str> {
+        self.sectors
+            .iter()
+            .find(|(_, s)| s.symbols.iter().any(|x| x == symbol))
+            .map(|(k, _)| k.as_str())
+            .or_else(|| {
+                self.benchmarks
+                    .symbols
+                    .iter()
+                    .any(|x| x == symbol)
+                    .then_some("benchmarks")
+            })
+    }
+
     /// The blended market-cap percentage as a fraction (0.0–1.0).
     pub fn mcap_pct(&self) -> f64 {
         self.settings.market_cap_weight_pct as f64 / 100.0
     }
+
+    /// The exchange a symbol trades on, falling back to the US default. A
+    /// symbol listed on several exchanges belongs to the first one.
+    pub fn exchange_for(&self, symbol: &str) -> &Exchange {
+        self.exchanges
+            .values()
+            .find(|e| e.symbols.iter().any(|s| s == symbol))
+            .unwrap_or_else(|| &self.exchanges[DEFAULT_EXCHANGE])
+    }
+
+    /// The exchange whose calendar defines index trading dates.
+    pub fn reference_exchange(&self) -> &Exchange {
+        &self.exchanges[&self.settings.reference_exchange]
+    }
+}
+
+/// The current `StocksConfig`, replaced whole on reload.
+///
+/// Callers `load` once per request or run and use that snapshot throughout,
+/// so a reload never mixes two configs within one computation.
+#[derive(Clone)]
+pub struct SharedConfig {
+    current: Arc<ArcSwap<StocksConfig>>,
+}
+
+impl SharedConfig {
+    pub fn new(config: StocksConfig) -> Self {
+        SharedConfig {
+            current: Arc::new(ArcSwap::from_pointee(config)),
+        }
+    }
+
+    pub fn load(&self) -> Arc<StocksConfig> {
+        self.current.load_full()
+    }
+
+    /// Swap in `config`, returning the one it replaced.
+    pub fn replace(&self, config: StocksConfig) -> Arc<StocksConfig> {
+        self.current.swap(Arc::new(config))
+    }
 }
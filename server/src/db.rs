@@ -1,71 +1,310 @@
-use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
-use std::str::FromStr;
+use crate::app_config::DatabaseConfig;
+use sqlx::any::AnyPoolOptions;
+use sqlx::migrate::Migrator;
+use sqlx::AnyPool;
+use std::time::Duration;
 
-pub async fn init_pool() -> Result<SqlitePool, sqlx::Error> {
-    let db_url = std::env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite:aiindex.db".to_string());
+/// Numbered schema migrations, embedded at compile time, one set per backend.
+static SQLITE_MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
+static POSTGRES_MIGRATOR: Migrator = sqlx::migrate!("./migrations/postgres");
 
-    let options = SqliteConnectOptions::from_str(&db_url)?.create_if_missing(true);
+/// Storage backend, selected by the `DATABASE_URL` scheme.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Sqlite,
+    Postgres,
+}
 
-    let pool = SqlitePoolOptions::new()
-        .max_connections(5)
-        .connect_with(options)
-        .await?;
+impl Backend {
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Sqlite => "sqlite",
+            Backend::Postgres => "postgres",
+        }
+    }
 
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS prices (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            symbol TEXT NOT NULL,
-            price REAL NOT NULL,
-            change REAL,
-            change_pct REAL,
-            market_cap REAL,
-            timestamp TEXT NOT NULL
-        )",
-    )
-    .execute(&pool)
-    .await?;
+    pub fn from_url(url: &str) -> Result<Self, sqlx::Error> {
+        match url.split(':').next().unwrap_or_default() {
+            "sqlite" => Ok(Backend::Sqlite),
+            // TimescaleDB speaks the Postgres protocol.
+            "postgres" | "postgresql" => Ok(Backend::Postgres),
+            other => Err(sqlx::Error::Configuration(
+                format!("unsupported DATABASE_URL scheme '{}'", other).into(),
+            )),
+        }
+    }
+
+    /// The backend a pool was opened against.
+    pub fn of(pool: &AnyPool) -> Self {
+        Self::from_url(pool.connect_options().database_url.as_str()).unwrap_or(Backend::Sqlite)
+    }
+
+    fn migrator(self) -> &'static Migrator {
+        match self {
+            Backend::Sqlite => &SQLITE_MIGRATOR,
+            Backend::Postgres => &POSTGRES_MIGRATOR,
+        }
+    }
+}
+
+pub async fn init_pool(config: &DatabaseConfig) -> Result<AnyPool, sqlx::Error> {
+    sqlx::any::install_default_drivers();
+
+    let mut db_url = config.url.clone();
+    let backend = Backend::from_url(&db_url)?;
+
+    // Create the SQLite file on first start unless the URL picks a mode.
+    if backend == Backend::Sqlite && !db_url.contains("mode=") {
+        db_url.push(if db_url.contains('?') { '&' } else { '?' });
+        db_url.push_str("mode=rwc");
+    }
 
-    sqlx::query("CREATE INDEX IF NOT EXISTS idx_prices_symbol_ts ON prices(symbol, timestamp)")
-        .execute(&pool)
+    // SQLite serializes writers, so a few connections cover concurrent readers;
+    // Postgres benefits from more.
+    let default_max = match backend {
+        Backend::Sqlite => 5,
+        Backend::Postgres => 10,
+    };
+    let max_connections = config.max_connections.unwrap_or(default_max);
+
+    let pool = AnyPoolOptions::new()
+        .max_connections(max_connections)
+        .min_connections(1)
+        .acquire_timeout(Duration::from_secs(10))
+        .idle_timeout(Duration::from_secs(10 * 60))
+        .after_connect(move |conn, _meta| {
+            Box::pin(async move {
+                if backend == Backend::Sqlite {
+                    // WAL lets the API read while a fetch cycle commits; the
+                    // busy timeout waits out the writer instead of failing.
+                    for pragma in [
+                        "PRAGMA journal_mode = WAL",
+                        "PRAGMA synchronous = NORMAL",
+                        "PRAGMA busy_timeout = 5000",
+                    ] {
+                        sqlx::query(pragma).execute(&mut *conn).await?;
+                    }
+                }
+                Ok(())
+            })
+        })
+        .connect(&db_url)
         .await?;
 
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS index_snapshots (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            value REAL NOT NULL,
-            daily_change REAL,
-            daily_change_pct REAL,
-            timestamp TEXT NOT NULL
-        )",
-    )
-    .execute(&pool)
-    .await?;
+    check_schema_version(&pool, backend).await?;
+    backend.migrator().run(&pool).await?;
 
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS base_prices (
-            symbol TEXT PRIMARY KEY,
-            price REAL NOT NULL,
-            recorded_at TEXT NOT NULL
-        )",
-    )
-    .execute(&pool)
-    .await?;
+    tracing::info!(
+        "Database initialized ({:?}) at schema version {}",
+        backend,
+        latest_version(backend)
+    );
+    Ok(pool)
+}
+
+/// The newest migration version this binary knows about.
+pub fn latest_version(backend: Backend) -> i64 {
+    backend
+        .migrator()
+        .iter()
+        .map(|m| m.version)
+        .max()
+        .unwrap_or(0)
+}
 
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS stock_profiles (
-            symbol TEXT PRIMARY KEY,
-            name TEXT,
-            exchange TEXT,
-            industry TEXT,
-            weburl TEXT,
-            logo TEXT,
-            country TEXT,
-            updated_at TEXT NOT NULL
-        )",
+/// Refuse to run against a database migrated by a newer build.
+async fn check_schema_version(pool: &AnyPool, backend: Backend) -> Result<(), sqlx::Error> {
+    let exists_sql = match backend {
+        Backend::Sqlite => {
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations'"
+        }
+        Backend::Postgres => {
+            "SELECT COUNT(*) FROM information_schema.tables
+             WHERE table_schema = current_schema() AND table_name = '_sqlx_migrations'"
+        }
+    };
+    let has_table = sqlx::query_as::<_, (i64,)>(exists_sql)
+        .fetch_one(pool)
+        .await?
+        .0
+        > 0;
+    if !has_table {
+        return Ok(());
+    }
+
+    let (applied,) = sqlx::query_as::<_, (Option<i64>,)>(
+        "SELECT MAX(version) FROM _sqlx_migrations WHERE success = TRUE",
     )
-    .execute(&pool)
+    .fetch_one(pool)
     .await?;
 
-    tracing::info!("Database initialized");
-    Ok(pool)
+    let latest = latest_version(backend);
+    match applied {
+        Some(v) if v > latest => Err(sqlx::Error::Configuration(
+            format!(
+                "database schema version {} is newer than this build supports ({})",
+                v, latest
+            )
+            .into(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// A migrated database for tests: a fresh SQLite file, or a fresh schema in
+/// the Postgres database named by `DATABASE_URL`. Removed on drop.
+#[cfg(test)]
+pub struct ScratchDb {
+    pub pool: AnyPool,
+    // Declared after the pool so it runs once the pool is gone.
+    #[allow(dead_code)]
+    cleanup: Cleanup,
+}
+
+#[cfg(test)]
+enum Cleanup {
+    File(std::path::PathBuf),
+    Schema(String, String),
+}
+
+#[cfg(test)]
+impl ScratchDb {
+    pub async fn sqlite() -> Self {
+        let path = std::env::temp_dir().join(format!("aiindex-test-{}.db", uuid::Uuid::new_v4()));
+        let config = DatabaseConfig {
+            url: format!("sqlite:{}", path.display()),
+            ..Default::default()
+        };
+        ScratchDb {
+            pool: init_pool(&config).await.expect("scratch SQLite database"),
+            cleanup: Cleanup::File(path),
+        }
+    }
+
+    /// `None` unless `DATABASE_URL` is a Postgres URL. With
+    /// `AIINDEX_REQUIRE_POSTGRES` set, as in CI, a missing URL panics
+    /// instead, so the Postgres suite cannot be skipped by accident.
+    pub async fn postgres() -> Option<Self> {
+        let url = std::env::var("DATABASE_URL")
+            .ok()
+            .filter(|url| Backend::from_url(url).ok() == Some(Backend::Postgres));
+        let url = match url {
+            Some(url) => url,
+            None if std::env::var_os("AIINDEX_REQUIRE_POSTGRES").is_some() => {
+                panic!("AIINDEX_REQUIRE_POSTGRES is set but DATABASE_URL is not a Postgres URL")
+            }
+            None => return None,
+        };
+        sqlx::any::install_default_drivers();
+        let schema = format!("aiindex_test_{}", uuid::Uuid::new_v4().simple());
+        let admin = AnyPoolOptions::new()
+            .max_connections(1)
+            .connect(&url)
+            .await
+            .expect("connect to DATABASE_URL");
+        sqlx::query(&format!("CREATE SCHEMA {}", schema))
+            .execute(&admin)
+            .await
+            .expect("create test schema");
+        admin.close().await;
+
+        let sep = if url.contains('?') { '&' } else { '?' };
+        let search_path = format!("{}{}options=-c%20search_path%3D{}", url, sep, schema);
+        // Created first so the schema is dropped even if migrations fail.
+        let cleanup = Cleanup::Schema(url, schema);
+        let config = DatabaseConfig {
+            url: search_path,
+            max_connections: Some(2),
+        };
+        Some(ScratchDb {
+            pool: init_pool(&config).await.expect("scratch Postgres schema"),
+            cleanup,
+        })
+    }
+}
+
+#[cfg(test)]
+impl Drop for Cleanup {
+    fn drop(&mut self) {
+        match self {
+            Cleanup::File(path) => {
+                for suffix in ["", "-wal", "-shm"] {
+                    let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+                }
+            }
+            Cleanup::Schema(url, schema) => {
+                // Drop runs inside the test's runtime, so clean up on a thread.
+                let (url, schema) = (url.clone(), schema.clone());
+                let _ = std::thread::spawn(move || {
+                    tokio::runtime::Runtime::new().unwrap().block_on(async {
+                        if let Ok(pool) =
+                            AnyPoolOptions::new().max_connections(1).connect(&url).await
+                        {
+                            let drop = format!("DROP SCHEMA IF EXISTS {} CASCADE", schema);
+                            let _ = sqlx::query(&drop).execute(&pool).await;
+                        }
+                    })
+                })
+                .join();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Re-run the timestamp normalization over rows an older build wrote.
+    async fn normalizes_timestamps(db: ScratchDb) {
+        let pool = &db.pool;
+        let backend = Backend::of(pool);
+        for (price, ts) in [
+            (1.0, "2024-03-11T14:00:00Z"),
+            (2.0, "2024-03-11T14:00:00+00:00"),
+            (3.0, "2024-03-11 15:00:00.000+00:00"),
+            (4.0, "2024-03-11T16:00:00.5Z"),
+        ] {
+            sqlx::query("INSERT INTO prices (symbol, price, timestamp) VALUES ('NVDA', $1, $2)")
+                .bind(price)
+                .bind(ts)
+                .execute(pool)
+                .await
+                .unwrap();
+        }
+        let migration = backend
+            .migrator()
+            .iter()
+            .find(|m| m.description == "normalize timestamps")
+            .expect("normalization migration");
+        sqlx::raw_sql(&migration.sql).execute(pool).await.unwrap();
+
+        let rows = sqlx::query_as::<_, (f64, String)>(
+            "SELECT price, timestamp FROM prices ORDER BY timestamp",
+        )
+        .fetch_all(pool)
+        .await
+        .unwrap();
+        let rows: Vec<(f64, &str)> = rows.iter().map(|(p, ts)| (*p, ts.as_str())).collect();
+        assert_eq!(
+            rows,
+            [
+                (2.0, "2024-03-11T14:00:00+00:00"),
+                (3.0, "2024-03-11T15:00:00+00:00"),
+                (4.0, "2024-03-11T16:00:00.5+00:00"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn sqlite_timestamps_are_normalized() {
+        normalizes_timestamps(ScratchDb::sqlite().await).await;
+    }
+
+    #[tokio::test]
+    async fn postgres_timestamps_are_normalized() {
+        if let Some(db) = ScratchDb::postgres().await {
+            normalizes_timestamps(db).await;
+        }
+    }
 }
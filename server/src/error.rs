@@ -0,0 +1,225 @@
+//! API error type, its JSON envelope, and request IDs.
+//!
+//! Every failed request answers with
+//! `{"error": {"status", "code", "message", "request_id"}}`. The request ID
+//! is taken from an incoming `x-request-id` header or generated, echoed in
+//! the response header, and attached to server-side error logs.
+
+use crate::backup::BackupError;
+use crate::export::ExportError;
+use crate::models::{ErrorBody, ErrorEnvelope};
+use axum::body::{Body, Bytes};
+use axum::extract::rejection::{JsonRejection, PathRejection, QueryRejection};
+use axum::extract::{FromRequest, FromRequestParts, Request};
+use axum::http::{header, HeaderName, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Json, Response};
+use serde::de::DeserializeOwned;
+
+pub static REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+#[derive(Debug)]
+pub enum ApiError {
+    /// Missing or wrong credentials. 401.
+    Unauthorized(String),
+    /// The credentials do not allow the operation. 403.
+    Forbidden(String),
+    /// The resource does not exist. 404.
+    NotFound(String),
+    /// A query or path parameter failed validation. 422.
+    Invalid(String),
+    /// The server has nothing to serve yet. 503.
+    Unavailable(String),
+    /// The deployment does not support the operation. 501.
+    NotImplemented(String),
+    /// Anything else. 500; details are logged, not returned.
+    Internal(String),
+}
+
+impl ApiError {
+    fn status(&self) -> StatusCode {
+        match self {
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Invalid(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::NotImplemented(_) => StatusCode::NOT_IMPLEMENTED,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Invalid(_) => "invalid_request",
+            ApiError::Unavailable(_) => "no_data",
+            ApiError::NotImplemented(_) => "not_implemented",
+            ApiError::Internal(_) => "internal",
+        }
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiError::Unauthorized(msg)
+            | ApiError::Forbidden(msg)
+            | ApiError::NotFound(msg)
+            | ApiError::Invalid(msg)
+            | ApiError::Unavailable(msg)
+            | ApiError::NotImplemented(msg)
+            | ApiError::Internal(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let request_id = REQUEST_ID.try_with(String::clone).ok();
+        let message = match &self {
+            ApiError::Internal(detail) => {
+                tracing::error!(
+                    "Request {} failed: {}",
+                    request_id.as_deref().unwrap_or("-"),
+                    detail
+                );
+                "internal server error".to_string()
+            }
+            other => other.to_string(),
+        };
+        let status = self.status();
+        let body = ErrorEnvelope {
+            error: ErrorBody {
+                status: status.as_u16(),
+                code: self.code().to_string(),
+                message,
+                request_id,
+            },
+        };
+        let mut response = (status, Json(body)).into_response();
+        if status == StatusCode::UNAUTHORIZED {
+            response
+                .headers_mut()
+                .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        }
+        response
+    }
+}
+
+/// Answers `/api` paths that match no route, so they get the JSON envelope
+/// instead of falling through to the static files.
+pub async fn not_found(uri: axum::http::Uri) -> ApiError {
+    ApiError::NotFound(format!("no API route at {}", uri.path()))
+}
+
+impl From<sqlx::Error> for ApiError {
+    fn from(e: sqlx::Error) -> Self {
+        ApiError::Internal(format!("database error: {}", e))
+    }
+}
+
+impl From<BackupError> for ApiError {
+    fn from(e: BackupError) -> Self {
+        match e {
+            BackupError::Unsupported(_) => ApiError::NotImplemented(e.to_string()),
+            BackupError::Invalid(_) => ApiError::Invalid(e.to_string()),
+            _ => ApiError::Internal(e.to_string()),
+        }
+    }
+}
+
+impl From<ExportError> for ApiError {
+    fn from(e: ExportError) -> Self {
+        match e {
+            ExportError::Invalid(msg) => ApiError::Invalid(msg),
+            _ => ApiError::Internal(e.to_string()),
+        }
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(e: QueryRejection) -> Self {
+        ApiError::Invalid(e.body_text())
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(e: PathRejection) -> Self {
+        ApiError::Invalid(e.body_text())
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(e: JsonRejection) -> Self {
+        ApiError::Invalid(e.body_text())
+    }
+}
+
+/// `axum::extract::Query` with rejections in the error envelope.
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(ApiError))]
+pub struct Query<T>(pub T);
+
+/// `axum::extract::Path` with rejections in the error envelope.
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(ApiError))]
+pub struct Path<T>(pub T);
+
+/// `axum::Json` as a request body, with rejections in the error envelope.
+#[derive(FromRequest)]
+#[from_request(via(axum::Json), rejection(ApiError))]
+pub struct JsonBody<T>(pub T);
+
+/// Like [`JsonBody`] for bodies whose fields are all optional: an empty body
+/// means `T::default()`.
+pub struct OptionalJsonBody<T>(pub T);
+
+impl<S, T> FromRequest<S> for OptionalJsonBody<T>
+where
+    S: Send + Sync,
+    T: DeserializeOwned + Default,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let (parts, body) = req.into_parts();
+        let bytes = Bytes::from_request(Request::from_parts(parts.clone(), body), state)
+            .await
+            .map_err(|e| ApiError::Invalid(e.body_text()))?;
+        if bytes.iter().all(u8::is_ascii_whitespace) {
+            return Ok(OptionalJsonBody(T::default()));
+        }
+        let req = Request::from_parts(parts, Body::from(bytes));
+        let JsonBody(value) = JsonBody::from_request(req, state).await?;
+        Ok(OptionalJsonBody(value))
+    }
+}
+
+/// Assign each request an ID, expose it to [`ApiError`] responses, and
+/// echo it in the `x-request-id` response header.
+pub async fn request_id(req: Request, next: Next) -> Response {
+    let id = req
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty() && v.len() <= 128)
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    let mut response = REQUEST_ID.scope(id.clone(), next.run(req)).await;
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response
+            .headers_mut()
+            .insert(REQUEST_ID_HEADER.clone(), value);
+    }
+    response
+}
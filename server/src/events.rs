@@ -0,0 +1,95 @@
+//! In-process broadcast of live updates, streamed to clients by `/api/stream`.
+
+use crate::models::{FetchRun, IndexSnapshot, PriceRecord};
+use std::str::FromStr;
+use tokio::sync::broadcast;
+
+/// Events buffered per subscriber before a slow one starts missing them.
+const CAPACITY: usize = 256;
+
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// A new index snapshot was stored.
+    Snapshot(IndexSnapshot),
+    /// A quote was committed in a fetch cycle.
+    Quote(PriceRecord),
+    /// A fetcher run started or finished.
+    Fetch(FetchRun),
+}
+
+impl Event {
+    pub fn topic(&self) -> Topic {
+        match self {
+            Event::Snapshot(_) => Topic::Snapshot,
+            Event::Quote(_) => Topic::Quote,
+            Event::Fetch(_) => Topic::Fetch,
+        }
+    }
+
+    /// The event payload as JSON.
+    pub fn data(&self) -> serde_json::Value {
+        match self {
+            Event::Snapshot(s) => serde_json::json!(s),
+            Event::Quote(q) => serde_json::json!(q),
+            Event::Fetch(r) => serde_json::json!(r),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Topic {
+    Snapshot,
+    Quote,
+    Fetch,
+}
+
+impl Topic {
+    pub const ALL: [Topic; 3] = [Topic::Snapshot, Topic::Quote, Topic::Fetch];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Topic::Snapshot => "snapshot",
+            Topic::Quote => "quote",
+            Topic::Fetch => "fetch",
+        }
+    }
+}
+
+impl FromStr for Topic {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Topic::ALL
+            .into_iter()
+            .find(|t| t.name() == s)
+            .ok_or_else(|| format!("unknown topic '{}' (expected snapshot, quote or fetch)", s))
+    }
+}
+
+/// Cheaply cloneable handle to the broadcast channel.
+#[derive(Clone)]
+pub struct Events {
+    tx: broadcast::Sender<Event>,
+}
+
+impl Events {
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(CAPACITY);
+        Events { tx }
+    }
+
+    /// Send an event to current subscribers. Having none is not an error.
+    pub fn publish(&self, event: Event) {
+        let _ = self.tx.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.tx.subscribe()
+    }
+}
+
+impl Default for Events {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,466 @@
+//! Bulk export of prices, snapshots, weights and profiles as CSV or Parquet.
+//!
+//! Rows are written in batches and handed to an async writer as each batch
+//! is encoded, so the HTTP download streams instead of building the file in
+//! memory first.
+
+use crate::config::StocksConfig;
+use crate::history::Range;
+use crate::index;
+use crate::models::{IndexSnapshot, PriceRecord, WeightPoint};
+use crate::store::Stores;
+use parquet::basic::Compression;
+use parquet::data_type::{ByteArray, ByteArrayType, DoubleType};
+use parquet::errors::ParquetError;
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+/// Rows per Parquet row group / write to the output.
+const BATCH_ROWS: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dataset {
+    Prices,
+    Snapshots,
+    Weights,
+    Profiles,
+}
+
+impl Dataset {
+    pub const ALL: [Dataset; 4] = [
+        Dataset::Prices,
+        Dataset::Snapshots,
+        Dataset::Weights,
+        Dataset::Profiles,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Dataset::Prices => "prices",
+            Dataset::Snapshots => "snapshots",
+            Dataset::Weights => "weights",
+            Dataset::Profiles => "profiles",
+        }
+    }
+
+    fn columns(self) -> &'static [(&'static str, Kind)] {
+        use Kind::*;
+        match self {
+            Dataset::Prices => &[
+                ("symbol", Text),
+                ("timestamp", Text),
+                ("price", Float),
+                ("change", Float),
+                ("change_pct", Float),
+                ("market_cap", Float),
+            ],
+            Dataset::Snapshots => &[
+                ("timestamp", Text),
+                ("value", Float),
+                ("daily_change", Float),
+                ("daily_change_pct", Float),
+            ],
+            Dataset::Weights => &[
+                ("timestamp", Text),
+                ("symbol", Text),
+                ("market_cap", Float),
+                ("weight", Float),
+            ],
+            Dataset::Profiles => &[
+                ("symbol", Text),
+                ("sector", Text),
+                ("name", Text),
+                ("exchange", Text),
+                ("industry", Text),
+                ("country", Text),
+                ("weburl", Text),
+                ("logo", Text),
+            ],
+        }
+    }
+}
+
+impl FromStr for Dataset {
+    type Err = ExportError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Dataset::ALL
+            .into_iter()
+            .find(|d| d.name() == s)
+            .ok_or_else(|| ExportError::Invalid(format!("unknown dataset '{}'", s)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Parquet,
+}
+
+impl Format {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::Parquet => "parquet",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Csv => "text/csv; charset=utf-8",
+            Format::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = ExportError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Format::Csv),
+            "parquet" => Ok(Format::Parquet),
+            _ => Err(ExportError::Invalid(format!("unknown format '{}'", s))),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ExportError {
+    /// Bad dataset, format or range.
+    Invalid(String),
+    Db(sqlx::Error),
+    Io(std::io::Error),
+    Csv(csv::Error),
+    Parquet(ParquetError),
+}
+
+impl std::fmt::Display for ExportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportError::Invalid(msg) => write!(f, "{}", msg),
+            ExportError::Db(e) => write!(f, "database error: {}", e),
+            ExportError::Io(e) => write!(f, "I/O error: {}", e),
+            ExportError::Csv(e) => write!(f, "CSV error: {}", e),
+            ExportError::Parquet(e) => write!(f, "Parquet error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ExportError {}
+
+impl From<sqlx::Error> for ExportError {
+    fn from(e: sqlx::Error) -> Self {
+        ExportError::Db(e)
+    }
+}
+
+impl From<std::io::Error> for ExportError {
+    fn from(e: std::io::Error) -> Self {
+        ExportError::Io(e)
+    }
+}
+
+impl From<csv::Error> for ExportError {
+    fn from(e: csv::Error) -> Self {
+        ExportError::Csv(e)
+    }
+}
+
+impl From<ParquetError> for ExportError {
+    fn from(e: ParquetError) -> Self {
+        ExportError::Parquet(e)
+    }
+}
+
+/// Write one dataset to `out` and return the number of rows exported.
+pub async fn write<W: AsyncWrite + Unpin>(
+    stores: &Stores,
+    config: &StocksConfig,
+    dataset: Dataset,
+    format: Format,
+    range: Range,
+    out: &mut W,
+) -> Result<usize, ExportError> {
+    let chunks = Chunks::default();
+    let columns = dataset.columns();
+    let mut sink: Box<dyn Sink + Send> = match format {
+        Format::Csv => Box::new(CsvSink::new(chunks.clone(), columns)?),
+        Format::Parquet => Box::new(ParquetSink::new(chunks.clone(), columns)?),
+    };
+
+    let (from, to) = range.bounds();
+    let mut pending: Vec<Row> = Vec::new();
+    let mut exported = 0;
+
+    match dataset {
+        Dataset::Prices => {
+            for sym in config.all_symbols() {
+                let rows = stores.prices.range(&sym, &from, &to).await?;
+                pending.extend(rows.into_iter().map(price_row));
+                if pending.len() >= BATCH_ROWS {
+                    exported += flush(&mut *sink, &mut pending, &chunks, out).await?;
+                }
+            }
+        }
+        Dataset::Snapshots => {
+            let rows = stores.snapshots.range(&from, &to).await?;
+            pending.extend(rows.into_iter().map(snapshot_row));
+        }
+        Dataset::Weights => {
+            let rows = index::weights_history(stores, config, range.from, range.to).await?;
+            pending.extend(rows.into_iter().map(weight_row));
+        }
+        Dataset::Profiles => {
+            for sym in config.all_symbols() {
+                let p = stores.profiles.get(&sym).await?.unwrap_or_default();
+                let sector = config.sector_of(&sym).map(str::to_string);
+                pending.push(vec![
+                    Value::Text(Some(sym)),
+                    Value::Text(sector),
+                    Value::Text(p.name),
+                    Value::Text(p.exchange),
+                    Value::Text(p.industry),
+                    Value::Text(p.country),
+                    Value::Text(p.weburl),
+                    Value::Text(p.logo),
+                ]);
+            }
+        }
+    }
+
+    while !pending.is_empty() {
+        let rest = pending.split_off(pending.len().min(BATCH_ROWS));
+        exported += flush(&mut *sink, &mut pending, &chunks, out).await?;
+        pending = rest;
+    }
+
+    sink.finish()?;
+    out.write_all(&chunks.take()).await?;
+    out.flush().await?;
+    Ok(exported)
+}
+
+/// Write each dataset to `<dir>/<dataset>.<ext>`.
+pub async fn to_files(
+    stores: &Stores,
+    config: &StocksConfig,
+    datasets: &[Dataset],
+    format: Format,
+    range: Range,
+    dir: &Path,
+) -> Result<(), ExportError> {
+    tokio::fs::create_dir_all(dir).await?;
+    for &dataset in datasets {
+        let path = dir.join(format!("{}.{}", dataset.name(), format.extension()));
+        let mut file = tokio::io::BufWriter::new(tokio::fs::File::create(&path).await?);
+        let rows = write(stores, config, dataset, format, range, &mut file).await?;
+        tracing::info!(
+            "Exported {} {} rows to {}",
+            rows,
+            dataset.name(),
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Encode pending rows and pass the bytes produced so far to `out`.
+async fn flush<W: AsyncWrite + Unpin>(
+    sink: &mut (dyn Sink + Send),
+    pending: &mut Vec<Row>,
+    chunks: &Chunks,
+    out: &mut W,
+) -> Result<usize, ExportError> {
+    let n = pending.len();
+    if n == 0 {
+        return Ok(0);
+    }
+    sink.write(pending)?;
+    pending.clear();
+    out.write_all(&chunks.take()).await?;
+    Ok(n)
+}
+
+fn price_row(r: PriceRecord) -> Row {
+    vec![
+        Value::Text(Some(r.symbol)),
+        Value::Text(Some(r.timestamp)),
+        Value::Float(Some(r.price)),
+        Value::Float(r.change),
+        Value::Float(r.change_pct),
+        Value::Float(r.market_cap),
+    ]
+}
+
+fn snapshot_row(s: IndexSnapshot) -> Row {
+    vec![
+        Value::Text(Some(s.timestamp)),
+        Value::Float(Some(s.value)),
+        Value::Float(s.daily_change),
+        Value::Float(s.daily_change_pct),
+    ]
+}
+
+fn weight_row(w: WeightPoint) -> Row {
+    vec![
+        Value::Text(Some(w.timestamp)),
+        Value::Text(Some(w.symbol)),
+        Value::Float(w.market_cap),
+        Value::Float(Some(w.weight)),
+    ]
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    Text,
+    Float,
+}
+
+#[derive(Debug, Clone)]
+enum Value {
+    Text(Option<String>),
+    Float(Option<f64>),
+}
+
+type Row = Vec<Value>;
+
+/// A `Write` target whose contents can be drained between batches.
+#[derive(Clone, Default)]
+struct Chunks(Arc<Mutex<Vec<u8>>>);
+
+impl Chunks {
+    fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+impl Write for Chunks {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+trait Sink {
+    /// Encode a batch; bytes become visible in the shared `Chunks`.
+    fn write(&mut self, rows: &[Row]) -> Result<(), ExportError>;
+
+    /// Write any trailer (the Parquet footer).
+    fn finish(self: Box<Self>) -> Result<(), ExportError>;
+}
+
+struct CsvSink {
+    writer: csv::Writer<Chunks>,
+}
+
+impl CsvSink {
+    fn new(chunks: Chunks, columns: &[(&str, Kind)]) -> Result<Self, ExportError> {
+        let mut writer = csv::Writer::from_writer(chunks);
+        writer.write_record(columns.iter().map(|(name, _)| *name))?;
+        writer.flush()?;
+        Ok(CsvSink { writer })
+    }
+}
+
+impl Sink for CsvSink {
+    fn write(&mut self, rows: &[Row]) -> Result<(), ExportError> {
+        for row in rows {
+            self.writer.write_record(row.iter().map(|v| match v {
+                Value::Text(s) => s.clone().unwrap_or_default(),
+                Value::Float(f) => f.map(|f| f.to_string()).unwrap_or_default(),
+            }))?;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<(), ExportError> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+struct ParquetSink {
+    writer: SerializedFileWriter<Chunks>,
+}
+
+impl ParquetSink {
+    fn new(chunks: Chunks, columns: &[(&str, Kind)]) -> Result<Self, ExportError> {
+        let fields: String = columns
+            .iter()
+            .map(|(name, kind)| match kind {
+                Kind::Text => format!("OPTIONAL BYTE_ARRAY {} (UTF8); ", name),
+                Kind::Float => format!("OPTIONAL DOUBLE {}; ", name),
+            })
+            .collect();
+        let schema = parse_message_type(&format!("message aiindex {{ {}}}", fields))?;
+        let props = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let writer = SerializedFileWriter::new(chunks, Arc::new(schema), Arc::new(props))?;
+        Ok(ParquetSink { writer })
+    }
+}
+
+impl Sink for ParquetSink {
+    fn write(&mut self, rows: &[Row]) -> Result<(), ExportError> {
+        let mut group = self.writer.next_row_group()?;
+        let mut idx = 0;
+        while let Some(mut col) = group.next_column()? {
+            // Every column is OPTIONAL: level 1 = present, 0 = null.
+            let defs: Vec<i16> = rows
+                .iter()
+                .map(|r| match &r[idx] {
+                    Value::Text(v) => v.is_some() as i16,
+                    Value::Float(v) => v.is_some() as i16,
+                })
+                .collect();
+            match rows.first().map(|r| &r[idx]) {
+                Some(Value::Text(_)) => {
+                    let values: Vec<ByteArray> = rows
+                        .iter()
+                        .filter_map(|r| match &r[idx] {
+                            Value::Text(Some(s)) => Some(ByteArray::from(s.as_str())),
+                            _ => None,
+                        })
+                        .collect();
+                    col.typed::<ByteArrayType>()
+                        .write_batch(&values, Some(&defs), None)?;
+                }
+                Some(Value::Float(_)) => {
+                    let values: Vec<f64> = rows
+                        .iter()
+                        .filter_map(|r| match &r[idx] {
+                            Value::Float(f) => *f,
+                            _ => None,
+                        })
+                        .collect();
+                    col.typed::<DoubleType>()
+                        .write_batch(&values, Some(&defs), None)?;
+                }
+                None => {}
+            }
+            col.close()?;
+            idx += 1;
+        }
+        group.close()?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), ExportError> {
+        self.writer.close()?;
+        Ok(())
+    }
+}
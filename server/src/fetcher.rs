@@ -1,184 +1,505 @@
-use crate::config::StocksConfig;
+use crate::app_config::AppConfig;
+use crate::cache::Cache;
+use crate::config::{Exchange, SharedConfig, StocksConfig};
+use crate::events::{Event, Events};
+use crate::history::Range;
 use crate::index;
-use crate::models::{FinnhubProfile, FinnhubQuote, YahooChartResponse};
-use chrono::{DateTime, Utc};
-use sqlx::SqlitePool;
+use crate::models::{
+    FetchRun, FinnhubProfile, FinnhubQuote, PriceRecord, StockProfile, YahooChartResponse,
+};
+use crate::store::{run_status, StoreResult, Stores};
+use chrono::{DateTime, NaiveDate, Utc};
 use std::sync::Arc;
+use tokio::sync::Mutex;
 use tokio::time::{self, Duration};
 
-const QUOTE_INTERVAL: Duration = Duration::from_secs(15 * 60); // 15 minutes
-const PROFILE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60); // 24 hours
-const CALL_SPACING: Duration = Duration::from_millis(50);
-
-pub fn spawn(pool: SqlitePool, config: Arc<StocksConfig>) {
-    let pool_q = pool.clone();
-    let config_q = config.clone();
-    tokio::spawn(async move {
-        quote_loop(pool_q, config_q).await;
-    });
-
-    tokio::spawn(async move {
-        // Daily profile refresh (the first fetch happens in quote_loop before quotes).
-        time::sleep(PROFILE_INTERVAL).await;
-        profile_loop(pool, config).await;
-    });
+/// Outcome of one quote, profile or backfill run, recorded in `fetch_runs`.
+struct RunLog<'a> {
+    stores: &'a Stores,
+    events: &'a Events,
+    id: Option<i64>,
+    kind: String,
+    started_at: String,
+    symbols: Vec<String>,
+    succeeded: i64,
+    failed: i64,
+    errors: Vec<String>,
 }
 
-async fn quote_loop(pool: SqlitePool, config: Arc<StocksConfig>) {
-    let client = reqwest::Client::new();
-    let api_key = std::env::var("FINNHUB_API_KEY").unwrap_or_default();
-    if api_key.is_empty() {
-        tracing::warn!("FINNHUB_API_KEY not set — fetcher will not run");
-        return;
+impl<'a> RunLog<'a> {
+    async fn start(
+        stores: &'a Stores,
+        events: &'a Events,
+        kind: &str,
+        symbols: &[String],
+    ) -> RunLog<'a> {
+        let now = Utc::now().to_rfc3339();
+        let id = match stores.runs.start(kind, symbols, &now).await {
+            Ok(id) => Some(id),
+            Err(e) => {
+                tracing::error!("Failed to record {} run start: {}", kind, e);
+                None
+            }
+        };
+        let run = RunLog {
+            stores,
+            events,
+            id,
+            kind: kind.to_string(),
+            started_at: now,
+            symbols: symbols.to_vec(),
+            succeeded: 0,
+            failed: 0,
+            errors: Vec::new(),
+        };
+        run.publish(None);
+        run
     }
 
-    // On first run, fetch profiles before quotes so market_cap is available.
-    fetch_all_profiles(&client, &api_key, &pool, &config).await;
+    /// Broadcast the run's state; `finished_at` is `None` while it runs.
+    fn publish(&self, finished_at: Option<String>) {
+        self.events.publish(Event::Fetch(FetchRun {
+            id: self.id.unwrap_or_default(),
+            kind: self.kind.clone(),
+            status: run_status(finished_at.is_some(), self.succeeded, self.failed),
+            started_at: self.started_at.clone(),
+            finished_at,
+            symbols: self.symbols.clone(),
+            succeeded: self.succeeded,
+            failed: self.failed,
+            errors: self.errors.clone(),
+        }));
+    }
+
+    fn ok(&mut self) {
+        self.succeeded += 1;
+    }
+
+    fn fail(&mut self, message: String) {
+        tracing::error!("{}", message);
+        self.failed += 1;
+        self.errors.push(message);
+    }
 
-    // Backfill historical data if the database is fresh.
-    backfill_history(&client, &api_key, &pool, &config).await;
+    /// Record an error that invalidates every success so far.
+    fn fail_all(&mut self, message: String) {
+        tracing::error!("{}", message);
+        self.failed += self.succeeded;
+        self.succeeded = 0;
+        self.errors.push(message);
+    }
 
-    loop {
-        fetch_all_quotes(&client, &api_key, &pool, &config).await;
-        index::compute_and_store(&pool, &config).await;
-        time::sleep(QUOTE_INTERVAL).await;
+    /// Record the outcome. Returns the succeeded and failed counts.
+    async fn finish(self) -> (i64, i64) {
+        let now = Utc::now().to_rfc3339();
+        self.publish(Some(now.clone()));
+        let counts = (self.succeeded, self.failed);
+        let Some(id) = self.id else {
+            return counts;
+        };
+        if let Err(e) = self
+            .stores
+            .runs
+            .finish(id, self.succeeded, self.failed, &self.errors, &now)
+            .await
+        {
+            tracing::error!("Failed to record run {} outcome: {}", id, e);
+        }
+        counts
     }
 }
 
-async fn profile_loop(pool: SqlitePool, config: Arc<StocksConfig>) {
-    let client = reqwest::Client::new();
-    let api_key = std::env::var("FINNHUB_API_KEY").unwrap_or_default();
-    if api_key.is_empty() {
-        return;
+/// Runs quote, profile and backfill work, on a schedule or on demand.
+///
+/// Clones share one run lock, so the background loops and admin-triggered
+/// jobs never fetch or recompute at the same time.
+#[derive(Clone)]
+pub struct Fetcher {
+    stores: Stores,
+    config: SharedConfig,
+    events: Events,
+    cache: Cache,
+    settings: Arc<AppConfig>,
+    client: reqwest::Client,
+    api_key: String,
+    /// Pause between Finnhub calls.
+    spacing: Duration,
+    busy: Arc<Mutex<()>>,
+}
+
+impl Fetcher {
+    pub fn new(
+        stores: Stores,
+        config: SharedConfig,
+        events: Events,
+        cache: Cache,
+        settings: Arc<AppConfig>,
+    ) -> Self {
+        Fetcher {
+            stores,
+            config,
+            events,
+            cache,
+            client: reqwest::Client::new(),
+            api_key: settings.finnhub.api_key.clone().unwrap_or_default(),
+            spacing: Duration::from_millis(settings.finnhub.call_spacing_ms),
+            settings,
+            busy: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Start the scheduled quote and profile loops.
+    pub fn spawn(&self) {
+        if !self.settings.fetcher.enabled {
+            tracing::info!("Scheduled fetching disabled");
+            return;
+        }
+        if self.api_key.is_empty() {
+            tracing::warn!("FINNHUB_API_KEY not set — fetcher will not run");
+            return;
+        }
+
+        let fetcher = self.clone();
+        tokio::spawn(async move {
+            // On first run, fetch profiles before quotes so market_cap is available.
+            let _ = fetcher.profiles().await;
+
+            // Backfill historical data if the database is fresh.
+            if fetcher.settings.fetcher.backfill_on_start {
+                fetcher.initial_backfill().await;
+            }
+
+            loop {
+                let _ = fetcher.quotes().await;
+                time::sleep(fetcher.settings.fetcher.quote_interval()).await;
+            }
+        });
+
+        let fetcher = self.clone();
+        tokio::spawn(async move {
+            // Daily profile refresh (the first fetch happens before the first quotes).
+            loop {
+                time::sleep(fetcher.settings.fetcher.profile_interval()).await;
+                let _ = fetcher.profiles().await;
+            }
+        });
+    }
+
+    /// Run one quote cycle, store its snapshot and announce it. Returns the
+    /// cycle timestamp.
+    pub async fn quotes(&self) -> Result<String, String> {
+        self.require_api_key()?;
+        let _busy = self.busy.lock().await;
+        let config = self.config.load();
+        let cycle = fetch_all_quotes(
+            &self.client,
+            &self.api_key,
+            self.spacing,
+            &self.stores,
+            &config,
+            &self.events,
+        )
+        .await
+        .ok_or_else(|| "no quotes were committed".to_string())?;
+
+        let snapshot = index::compute_and_store(&self.stores, &config, &cycle)
+            .await
+            .unwrap_or_else(|e| {
+                tracing::error!("Index computation failed: {}", e);
+                None
+            });
+        self.refresh_cache().await;
+        // Announce the snapshot once the cache serves it.
+        if let Some(snapshot) = snapshot {
+            self.events.publish(Event::Snapshot(snapshot));
+        }
+        Ok(cycle)
+    }
+
+    /// Refresh every profile and market cap. Returns the succeeded and
+    /// failed counts.
+    pub async fn profiles(&self) -> Result<(i64, i64), String> {
+        let symbols = self.config.load().all_symbols();
+        self.profiles_for(&symbols).await
+    }
+
+    /// Refresh the profiles and market caps of `symbols`.
+    async fn profiles_for(&self, symbols: &[String]) -> Result<(i64, i64), String> {
+        self.require_api_key()?;
+        let _busy = self.busy.lock().await;
+        let counts = fetch_profiles(
+            &self.client,
+            &self.api_key,
+            self.spacing,
+            &self.stores,
+            symbols,
+            &self.events,
+        )
+        .await;
+        self.refresh_cache().await;
+        Ok(counts)
+    }
+
+    /// Backfill daily history for `symbols` over `range`, or the last year,
+    /// then recompute the affected snapshots. Returns the succeeded and
+    /// failed counts.
+    pub async fn backfill(&self, symbols: &[String], range: Option<Range>) -> (i64, i64) {
+        let config = self.config.load();
+        let since = range.map(|r| config.reference_exchange().trading_date(r.from));
+        self.backfill_since(symbols, range, since).await
+    }
+
+    /// Backfill the last year for `symbols`, recomputing snapshots from
+    /// `since` onward, or all of them.
+    async fn backfill_since(
+        &self,
+        symbols: &[String],
+        range: Option<Range>,
+        since: Option<NaiveDate>,
+    ) -> (i64, i64) {
+        let _busy = self.busy.lock().await;
+        let counts = backfill(
+            &self.client,
+            &self.stores,
+            &self.config.load(),
+            &self.events,
+            symbols,
+            range,
+            since,
+        )
+        .await;
+        self.history_rewritten().await;
+        counts
+    }
+
+    /// Recompute index snapshots from stored prices, from `since` onward or
+    /// entirely. Returns the number of snapshots written.
+    pub async fn recompute(&self, since: Option<NaiveDate>) -> StoreResult<usize> {
+        let _busy = self.busy.lock().await;
+        let days = index::compute_history(&self.stores, &self.config.load(), since).await?;
+        self.history_rewritten().await;
+        Ok(days)
+    }
+
+    /// Backfill ~1 year of daily history if the database has no historical data.
+    async fn initial_backfill(&self) {
+        // Check if we already have historical data spanning more than 7 days.
+        let span = match self.stores.snapshots.span().await {
+            Ok(span) => span,
+            Err(e) => {
+                tracing::error!("Failed to read snapshot span, skipping backfill: {}", e);
+                return;
+            }
+        };
+        let date_span = span
+            .and_then(|(min, max)| {
+                let min = DateTime::parse_from_rfc3339(&min).ok()?;
+                let max = DateTime::parse_from_rfc3339(&max).ok()?;
+                Some((max - min).num_days())
+            })
+            .unwrap_or(0);
+
+        if date_span > 7 {
+            tracing::info!(
+                "Database spans {} days of history, skipping backfill",
+                date_span
+            );
+            return;
+        }
+        self.backfill(&self.config.load().all_symbols(), None).await;
+    }
+
+    /// Re-read `stocks.toml` and swap it in, then fetch profiles and a year
+    /// of history for any symbols it adds. Returns a summary of the change.
+    ///
+    /// Published index history is kept: added constituents count from the
+    /// current session on and removed ones stop counting, so the index steps
+    /// on the reload day. The summary says when a full recompute is required
+    /// to restate earlier sessions with the new constituents.
+    pub async fn reload_config(&self) -> Result<String, String> {
+        let next = StocksConfig::load(self.settings.stocks.path.as_deref())
+            .map_err(|e| format!("failed to load config: {}", e))?;
+        let next_symbols = next.all_symbols();
+        let mut next_constituents = next.index_symbols();
+        let previous = self.config.replace(next);
+        let previous_symbols = previous.all_symbols();
+        let mut previous_constituents = previous.index_symbols();
+        next_constituents.sort();
+        previous_constituents.sort();
+        self.refresh_cache().await;
+
+        let added: Vec<String> = next_symbols
+            .iter()
+            .filter(|s| !previous_symbols.contains(s))
+            .cloned()
+            .collect();
+        let removed = previous_symbols
+            .iter()
+            .filter(|s| !next_symbols.contains(s))
+            .count();
+        tracing::info!(
+            "Config reloaded: {} symbols, {} added, {} removed",
+            next_symbols.len(),
+            added.len(),
+            removed
+        );
+
+        if !added.is_empty() {
+            // Profiles first, so the backfilled history is weighted by market cap.
+            if let Err(e) = self.profiles_for(&added).await {
+                tracing::warn!("Skipping profiles for new symbols: {}", e);
+            }
+            let today = self
+                .config
+                .load()
+                .reference_exchange()
+                .trading_date(Utc::now());
+            self.backfill_since(&added, None, Some(today)).await;
+        }
+        let mut summary = format!("{} symbols added, {} removed", added.len(), removed);
+        // Benchmarks are not weighted, so only constituent changes move the index.
+        if previous_constituents != next_constituents {
+            summary.push_str(
+                "; index history predates the change, so a full recompute is required \
+                 to restate it without a step on the reload day",
+            );
+        }
+        Ok(summary)
+    }
+
+    fn require_api_key(&self) -> Result<(), String> {
+        if self.api_key.is_empty() {
+            return Err("FINNHUB_API_KEY is not set".to_string());
+        }
+        Ok(())
     }
 
-    loop {
-        fetch_all_profiles(&client, &api_key, &pool, &config).await;
-        time::sleep(PROFILE_INTERVAL).await;
+    /// Invalidate cached history responses, then rebuild the cache.
+    async fn history_rewritten(&self) {
+        if let Err(e) = self.stores.snapshots.bump_data_version().await {
+            tracing::error!("Failed to bump the data version: {}", e);
+        }
+        self.refresh_cache().await;
+    }
+
+    /// Rebuild the latest-state cache after a run has committed.
+    async fn refresh_cache(&self) {
+        if let Err(e) = self.cache.refresh(&self.stores, &self.config.load()).await {
+            tracing::error!("Failed to refresh latest-state cache: {}", e);
+        }
     }
 }
 
+/// Fetch every quote, then commit the cycle atomically. Returns the cycle
+/// timestamp once the prices are committed.
 async fn fetch_all_quotes(
     client: &reqwest::Client,
     api_key: &str,
-    pool: &SqlitePool,
+    spacing: Duration,
+    stores: &Stores,
     config: &StocksConfig,
-) {
+    events: &Events,
+) -> Option<String> {
     let symbols = config.all_symbols();
     let now = Utc::now().to_rfc3339();
     tracing::info!("Fetching quotes for {} symbols", symbols.len());
+    let mut run = RunLog::start(stores, events, "quotes", &symbols).await;
 
+    let mut records = Vec::with_capacity(symbols.len());
     for symbol in &symbols {
         match fetch_quote(client, api_key, symbol).await {
             Ok(q) => {
                 if q.c <= 0.0 {
-                    tracing::warn!("{}: price is zero, skipping", symbol);
+                    run.fail(format!("{}: price is zero, skipping", symbol));
+                    time::sleep(spacing).await;
                     continue;
                 }
-                // Carry forward last known market_cap.
-                let prev_mcap = sqlx::query_as::<_, (Option<f64>,)>(
-                    "SELECT market_cap FROM prices WHERE symbol = ? AND market_cap IS NOT NULL ORDER BY timestamp DESC LIMIT 1",
-                )
-                .bind(symbol)
-                .fetch_optional(pool)
-                .await
-                .ok()
-                .flatten()
-                .and_then(|(m,)| m);
-
-                if let Err(e) = sqlx::query(
-                    "INSERT INTO prices (symbol, price, change, change_pct, market_cap, timestamp)
-                     VALUES (?, ?, ?, ?, ?, ?)",
-                )
-                .bind(symbol)
-                .bind(q.c)
-                .bind(q.d)
-                .bind(q.dp)
-                .bind(prev_mcap)
-                .bind(&now)
-                .execute(pool)
-                .await
-                {
-                    tracing::error!("{}: failed to insert price: {}", symbol, e);
-                }
-
-                // Record base price if not yet set.
-                let _ = sqlx::query(
-                    "INSERT OR IGNORE INTO base_prices (symbol, price, recorded_at)
-                     VALUES (?, ?, ?)",
-                )
-                .bind(symbol)
-                .bind(q.c)
-                .bind(&now)
-                .execute(pool)
-                .await;
+                // Market cap is carried forward from the last known row on commit.
+                records.push(PriceRecord {
+                    symbol: symbol.clone(),
+                    price: q.c,
+                    change: q.d,
+                    change_pct: q.dp,
+                    market_cap: None,
+                    timestamp: now.clone(),
+                });
+                run.ok();
             }
             Err(e) => {
-                tracing::error!("{}: quote fetch failed: {}", symbol, e);
+                run.fail(format!("{}: quote fetch failed: {}", symbol, e));
             }
         }
-        time::sleep(CALL_SPACING).await;
+        time::sleep(spacing).await;
+    }
+
+    if records.is_empty() {
+        tracing::warn!("Quote fetch cycle returned no prices");
+        run.finish().await;
+        return None;
     }
 
-    tracing::info!("Quote fetch cycle complete");
+    let committed = match stores.prices.record_cycle(&records).await {
+        Ok(()) => {
+            tracing::info!("Quote fetch cycle complete: {} prices", records.len());
+            for record in records {
+                events.publish(Event::Quote(record));
+            }
+            Some(now)
+        }
+        Err(e) => {
+            run.fail_all(format!("Failed to commit quote cycle: {}", e));
+            None
+        }
+    };
+    run.finish().await;
+    committed
 }
 
-async fn fetch_all_profiles(
+async fn fetch_profiles(
     client: &reqwest::Client,
     api_key: &str,
-    pool: &SqlitePool,
-    config: &StocksConfig,
-) {
-    let symbols = config.all_symbols();
+    spacing: Duration,
+    stores: &Stores,
+    symbols: &[String],
+    events: &Events,
+) -> (i64, i64) {
     tracing::info!("Fetching profiles for {} symbols", symbols.len());
+    let mut run = RunLog::start(stores, events, "profiles", symbols).await;
 
     let now = Utc::now().to_rfc3339();
-    for symbol in &symbols {
+    for symbol in symbols {
         match fetch_profile(client, api_key, symbol).await {
             Ok(p) => {
                 if let Some(mcap) = p.market_capitalization {
                     // Update the most recent price row for this symbol with market cap.
-                    let _ = sqlx::query(
-                        "UPDATE prices SET market_cap = ?
-                         WHERE id = (SELECT id FROM prices WHERE symbol = ? ORDER BY timestamp DESC LIMIT 1)",
-                    )
-                    .bind(mcap)
-                    .bind(symbol)
-                    .execute(pool)
-                    .await;
+                    if let Err(e) = stores.prices.set_latest_market_cap(symbol, mcap).await {
+                        tracing::error!("{}: failed to update market cap: {}", symbol, e);
+                    }
                 }
                 // Store profile info.
-                let _ = sqlx::query(
-                    "INSERT INTO stock_profiles (symbol, name, exchange, industry, weburl, logo, country, updated_at)
-                     VALUES (?, ?, ?, ?, ?, ?, ?, ?)
-                     ON CONFLICT(symbol) DO UPDATE SET
-                       name = excluded.name,
-                       exchange = excluded.exchange,
-                       industry = excluded.industry,
-                       weburl = excluded.weburl,
-                       logo = excluded.logo,
-                       country = excluded.country,
-                       updated_at = excluded.updated_at",
-                )
-                .bind(symbol)
-                .bind(&p.name)
-                .bind(&p.exchange)
-                .bind(&p.finnhub_industry)
-                .bind(&p.weburl)
-                .bind(&p.logo)
-                .bind(&p.country)
-                .bind(&now)
-                .execute(pool)
-                .await;
+                let profile = StockProfile {
+                    name: p.name,
+                    exchange: p.exchange,
+                    industry: p.finnhub_industry,
+                    weburl: p.weburl,
+                    logo: p.logo,
+                    country: p.country,
+                };
+                match stores.profiles.upsert(symbol, &profile, &now).await {
+                    Ok(()) => run.ok(),
+                    Err(e) => run.fail(format!("{}: failed to store profile: {}", symbol, e)),
+                }
             }
             Err(e) => {
-                tracing::error!("{}: profile fetch failed: {}", symbol, e);
+                run.fail(format!("{}: profile fetch failed: {}", symbol, e));
             }
         }
-        time::sleep(CALL_SPACING).await;
+        time::sleep(spacing).await;
     }
 
+    let counts = run.finish().await;
     tracing::info!("Profile fetch cycle complete");
+    counts
 }
 
 async fn fetch_quote(
@@ -213,10 +534,19 @@ async fn fetch_profile(
 async fn fetch_yahoo_chart(
     client: &reqwest::Client,
     symbol: &str,
+    range: Option<Range>,
 ) -> Result<YahooChartResponse, reqwest::Error> {
+    let window = match range {
+        Some(r) => format!(
+            "period1={}&period2={}",
+            r.from.timestamp(),
+            r.to.min(Utc::now()).timestamp()
+        ),
+        None => "range=1y".to_string(),
+    };
     let url = format!(
-        "https://query1.finance.yahoo.com/v8/finance/chart/{}?range=1y&interval=1d",
-        symbol
+        "https://query1.finance.yahoo.com/v8/finance/chart/{}?{}&interval=1d",
+        symbol, window
     );
     client
         .get(&url)
@@ -227,42 +557,134 @@ async fn fetch_yahoo_chart(
         .await
 }
 
-/// Backfill ~1 year of daily history if the database has no historical data.
-async fn backfill_history(
-    client: &reqwest::Client,
-    _api_key: &str,
-    pool: &SqlitePool,
-    config: &StocksConfig,
-) {
-    // Check if we already have historical data spanning more than 7 days.
-    let date_span = sqlx::query_as::<_, (i64,)>(
-        "SELECT CAST((julianday(MAX(timestamp)) - julianday(MIN(timestamp))) AS INTEGER) FROM index_snapshots",
-    )
-    .fetch_one(pool)
-    .await
-    .map(|(d,)| d)
-    .unwrap_or(0);
-
-    if date_span > 7 {
-        tracing::info!(
-            "Database spans {} days of history, skipping backfill",
-            date_span
-        );
-        return;
+/// When a Yahoo daily bar's session closed. Yahoo stamps bars at the session
+/// open; they are stored at the close. `None` for a session that has not
+/// closed by `now`, whose bar is still moving.
+fn bar_close(exchange: &Exchange, ts: i64, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let close = exchange.session_close(exchange.trading_date(DateTime::from_timestamp(ts, 0)?));
+    (close <= now).then_some(close)
+}
+
+/// Store one symbol's Yahoo daily closes that fall in `range`, recording its
+/// base price, and return how many were stored.
+async fn store_closes(
+    stores: &Stores,
+    exchange: &Exchange,
+    symbol: &str,
+    closes: &[Option<f64>],
+    timestamps: &[i64],
+    range: Option<Range>,
+    now: DateTime<Utc>,
+) -> usize {
+    let close_ts = |ts: i64| bar_close(exchange, ts, now).map(|c| c.to_rfc3339());
+
+    // Find first valid close for base price.
+    let first_valid = closes
+        .iter()
+        .zip(timestamps.iter())
+        .find(|(c, _)| c.is_some());
+    if let Some((Some(base_price), Some(base_ts))) = first_valid.map(|(c, ts)| (c, close_ts(*ts))) {
+        if let Err(e) = stores
+            .prices
+            .insert_base_price(symbol, *base_price, &base_ts)
+            .await
+        {
+            tracing::error!("{}: failed to record base price: {}", symbol, e);
+        }
+    }
+
+    // Look up current market_cap for this symbol.
+    let mcap = stores
+        .prices
+        .latest_market_cap(symbol)
+        .await
+        .unwrap_or_default();
+
+    let mut count = 0;
+    let mut prev_close: Option<f64> = None;
+    for (close_opt, ts) in closes.iter().zip(timestamps.iter()) {
+        if let Some(r) = range {
+            if *ts < r.from.timestamp() || *ts >= r.to.timestamp() {
+                continue;
+            }
+        }
+        let price = match close_opt {
+            Some(p) if *p > 0.0 => *p,
+            _ => {
+                continue;
+            }
+        };
+        // Today's bar is the session in progress; live quotes cover it.
+        let Some(close) = bar_close(exchange, *ts, now) else {
+            continue;
+        };
+
+        // A ranged backfill can start mid-history, so the first bar chains
+        // onto the close stored before its session.
+        if count == 0 {
+            let open = exchange.session_open(exchange.trading_date(close));
+            prev_close = match stores.prices.before(symbol, &open.to_rfc3339()).await {
+                Ok(row) => row.map(|r| r.price),
+                Err(e) => {
+                    tracing::error!("{}: failed to read the prior close: {}", symbol, e);
+                    None
+                }
+            };
+        }
+
+        let (change, change_pct) = match prev_close {
+            Some(prev) if prev > 0.0 => {
+                let chg = price - prev;
+                let pct = (chg / prev) * 100.0;
+                (Some(chg), Some(pct))
+            }
+            _ => (None, None),
+        };
+
+        let record = PriceRecord {
+            symbol: symbol.to_string(),
+            price,
+            change,
+            change_pct,
+            market_cap: mcap,
+            timestamp: close.to_rfc3339(),
+        };
+        if let Err(e) = stores.prices.upsert(&record).await {
+            tracing::error!("{}: failed to insert backfill price: {}", symbol, e);
+            continue;
+        }
+
+        prev_close = Some(price);
+        count += 1;
     }
+    count
+}
 
+/// Store daily closes from Yahoo Finance for `symbols` over `range` (the
+/// last year when `None`), then recompute snapshots from the earliest
+/// session fetched.
+async fn backfill(
+    client: &reqwest::Client,
+    stores: &Stores,
+    config: &StocksConfig,
+    events: &Events,
+    symbols: &[String],
+    range: Option<Range>,
+    since: Option<NaiveDate>,
+) -> (i64, i64) {
     tracing::info!("Backfilling historical data via Yahoo Finance...");
 
     let now = Utc::now();
-    let symbols = config.all_symbols();
+    let mut run = RunLog::start(stores, events, "backfill", symbols).await;
 
-    for symbol in &symbols {
-        match fetch_yahoo_chart(client, symbol).await {
+    for symbol in symbols {
+        let exchange = config.exchange_for(symbol);
+        match fetch_yahoo_chart(client, symbol, range).await {
             Ok(resp) => {
                 let result = match resp.chart.result.as_ref().and_then(|r| r.first()) {
                     Some(r) => r,
                     None => {
-                        tracing::warn!("{}: no Yahoo chart data", symbol);
+                        run.fail(format!("{}: no Yahoo chart data", symbol));
                         continue;
                     }
                 };
@@ -276,82 +698,21 @@ async fn backfill_history(
                     .unwrap_or_default();
 
                 if closes.is_empty() || timestamps.is_empty() {
+                    run.fail(format!("{}: empty Yahoo chart data", symbol));
                     continue;
                 }
 
-                // Find first valid close for base price.
-                let first_valid = closes
-                    .iter()
-                    .zip(timestamps.iter())
-                    .find(|(c, _)| c.is_some());
-                if let Some((Some(base_price), base_ts)) = first_valid {
-                    let _ = sqlx::query(
-                        "INSERT OR IGNORE INTO base_prices (symbol, price, recorded_at)
-                         VALUES (?, ?, ?)",
-                    )
-                    .bind(symbol)
-                    .bind(base_price)
-                    .bind(
-                        DateTime::from_timestamp(*base_ts, 0)
-                            .unwrap_or(now)
-                            .to_rfc3339(),
-                    )
-                    .execute(pool)
-                    .await;
-                }
-
-                // Look up current market_cap for this symbol.
-                let mcap = sqlx::query_as::<_, (Option<f64>,)>(
-                    "SELECT market_cap FROM prices WHERE symbol = ? AND market_cap IS NOT NULL ORDER BY timestamp DESC LIMIT 1",
-                )
-                .bind(symbol)
-                .fetch_optional(pool)
-                .await
-                .ok()
-                .flatten()
-                .and_then(|(m,)| m);
-
-                let mut count = 0;
-                let mut prev_close: Option<f64> = None;
-                for (close_opt, ts) in closes.iter().zip(timestamps.iter()) {
-                    let price = match close_opt {
-                        Some(p) if *p > 0.0 => *p,
-                        _ => {
-                            continue;
-                        }
-                    };
-
-                    let dt = DateTime::from_timestamp(*ts, 0).unwrap_or(now).to_rfc3339();
-
-                    let (change, change_pct) = match prev_close {
-                        Some(prev) if prev > 0.0 => {
-                            let chg = price - prev;
-                            let pct = (chg / prev) * 100.0;
-                            (Some(chg), Some(pct))
-                        }
-                        _ => (None, None),
-                    };
-
-                    let _ = sqlx::query(
-                        "INSERT INTO prices (symbol, price, change, change_pct, market_cap, timestamp)
-                         VALUES (?, ?, ?, ?, ?, ?)",
-                    )
-                    .bind(symbol)
-                    .bind(price)
-                    .bind(change)
-                    .bind(change_pct)
-                    .bind(mcap)
-                    .bind(&dt)
-                    .execute(pool)
-                    .await;
-
-                    prev_close = Some(price);
-                    count += 1;
-                }
+                let count =
+                    store_closes(stores, exchange, symbol, closes, timestamps, range, now).await;
                 tracing::info!("{}: backfilled {} daily points", symbol, count);
+                if count > 0 {
+                    run.ok();
+                } else {
+                    run.fail(format!("{}: no daily points stored", symbol));
+                }
             }
             Err(e) => {
-                tracing::error!("{}: Yahoo chart fetch failed: {}", symbol, e);
+                run.fail(format!("{}: Yahoo chart fetch failed: {}", symbol, e));
             }
         }
         // Slightly longer spacing for Yahoo to be polite.
@@ -360,106 +721,85 @@ async fn backfill_history(
 
     // Compute historical index snapshots from backfilled data.
     tracing::info!("Computing historical index snapshots...");
-    let dates = sqlx::query_as::<_, (String,)>(
-        "SELECT DISTINCT DATE(timestamp) as d FROM prices ORDER BY d",
-    )
-    .fetch_all(pool)
-    .await
-    .unwrap_or_default();
-
-    for (date_str,) in &dates {
-        let index_symbols = config.index_symbols();
-        let mcap_pct = config.mcap_pct();
-        let base_value = config.settings.base_value;
-        let n = index_symbols.len() as f64;
-        if n == 0.0 {
-            continue;
-        }
+    match index::compute_history(stores, config, since).await {
+        Ok(days) => tracing::info!("Backfill complete: {} trading days", days),
+        Err(e) => run.fail_all(format!("Historical index computation failed: {}", e)),
+    }
+    run.finish().await
+}
 
-        let mut entries: Vec<(f64, f64, f64)> = Vec::new();
-
-        for sym in &index_symbols {
-            let latest = sqlx::query_as::<_, (f64, Option<f64>)>(
-                "SELECT price, market_cap FROM prices
-                 WHERE symbol = ? AND DATE(timestamp) <= ?
-                 ORDER BY timestamp DESC LIMIT 1",
-            )
-            .bind(sym)
-            .bind(date_str)
-            .fetch_optional(pool)
-            .await
-            .ok()
-            .flatten();
-
-            let base =
-                sqlx::query_as::<_, (f64,)>("SELECT price FROM base_prices WHERE symbol = ?")
-                    .bind(sym)
-                    .fetch_optional(pool)
-                    .await
-                    .ok()
-                    .flatten();
-
-            if let (Some((current_price, mcap_opt)), Some((base_price,))) = (latest, base) {
-                if base_price > 0.0 && current_price > 0.0 {
-                    let mcap = mcap_opt.unwrap_or(1.0);
-                    entries.push((current_price, base_price, mcap));
-                }
-            }
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        if entries.is_empty() {
-            continue;
-        }
+    fn utc(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
 
-        let total_mcap: f64 = entries.iter().map(|(_, _, m)| m).sum();
-        let equal_weight = 1.0 / entries.len() as f64;
+    #[test]
+    fn bars_are_stamped_at_their_close_once_it_has_passed() {
+        let x = Exchange::us();
+        // Yahoo's bar for Tuesday 2024-03-12, stamped at the open.
+        let open = utc("2024-03-12T13:30:00Z").timestamp();
+        let close = utc("2024-03-12T20:00:00Z");
+        assert_eq!(
+            bar_close(&x, open, utc("2024-03-13T00:00:00Z")),
+            Some(close)
+        );
+        assert_eq!(bar_close(&x, open, close), Some(close));
+        // Mid-session the bar is still moving.
+        assert_eq!(bar_close(&x, open, utc("2024-03-12T17:00:00Z")), None);
+    }
 
-        let mut index_value = 0.0;
-        for (current, base, mcap) in &entries {
-            let mcap_weight = if total_mcap > 0.0 {
-                mcap / total_mcap
-            } else {
-                equal_weight
+    #[tokio::test]
+    async fn ranged_backfill_chains_onto_the_stored_close() {
+        let stores = Stores::memory();
+        for (price, change, ts) in [
+            (100.0, None, "2024-03-08T21:00:00+00:00"),
+            (104.0, Some(4.0), "2024-03-11T20:00:00+00:00"),
+        ] {
+            let row = PriceRecord {
+                symbol: "NVDA".to_string(),
+                price,
+                change,
+                change_pct: change,
+                market_cap: Some(1e12),
+                timestamp: ts.to_string(),
             };
-            let blended = (mcap_pct * mcap_weight) + ((1.0 - mcap_pct) * equal_weight);
-            index_value += blended * (current / base);
+            stores.prices.upsert(&row).await.unwrap();
         }
-        index_value *= base_value;
-
-        let prev = sqlx::query_as::<_, (f64,)>(
-            "SELECT value FROM index_snapshots WHERE DATE(timestamp) < ? ORDER BY timestamp DESC LIMIT 1",
-        )
-        .bind(date_str)
-        .fetch_optional(pool)
-        .await
-        .ok()
-        .flatten();
 
-        let (daily_change, daily_change_pct) = if let Some((prev_val,)) = prev {
-            if prev_val > 0.0 {
-                let chg = index_value - prev_val;
-                let pct = (chg / prev_val) * 100.0;
-                (Some(chg), Some(pct))
-            } else {
-                (None, None)
-            }
-        } else {
-            (None, None)
+        // Yahoo's bars for Friday, Monday and Tuesday; only Monday is in range.
+        let timestamps = [
+            utc("2024-03-08T14:30:00Z").timestamp(),
+            utc("2024-03-11T13:30:00Z").timestamp(),
+            utc("2024-03-12T13:30:00Z").timestamp(),
+        ];
+        let closes = [Some(99.0), Some(110.0), Some(120.0)];
+        let range = Range {
+            from: utc("2024-03-11T00:00:00Z"),
+            to: utc("2024-03-12T00:00:00Z"),
         };
-
-        let snap_ts = format!("{}T16:00:00+00:00", date_str);
-
-        let _ = sqlx::query(
-            "INSERT INTO index_snapshots (value, daily_change, daily_change_pct, timestamp)
-             VALUES (?, ?, ?, ?)",
+        let stored = store_closes(
+            &stores,
+            &Exchange::us(),
+            "NVDA",
+            &closes,
+            &timestamps,
+            Some(range),
+            utc("2024-03-13T00:00:00Z"),
         )
-        .bind(index_value)
-        .bind(daily_change)
-        .bind(daily_change_pct)
-        .bind(&snap_ts)
-        .execute(pool)
         .await;
-    }
+        assert_eq!(stored, 1);
 
-    tracing::info!("Backfill complete: {} trading days", dates.len());
+        let rows: Vec<(f64, Option<f64>)> = stores
+            .prices
+            .series("NVDA")
+            .await
+            .unwrap()
+            .iter()
+            .map(|r| (r.price, r.change))
+            .collect();
+        assert_eq!(rows, [(100.0, None), (110.0, Some(10.0))]);
+    }
 }
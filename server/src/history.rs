@@ -0,0 +1,161 @@
+//! Time ranges and OHLC resampling for history endpoints.
+
+use crate::config::Exchange;
+use crate::models::{OhlcBar, OhlcPoint};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use std::str::FromStr;
+
+/// Half-open time window `[from, to)`.
+#[derive(Debug, Clone, Copy)]
+pub struct Range {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+}
+
+impl Range {
+    /// Parse optional bounds given as RFC 3339 timestamps or `YYYY-MM-DD`
+    /// dates (midnight UTC). Missing bounds leave that side open.
+    pub fn parse(from: Option<&str>, to: Option<&str>) -> Result<Self, String> {
+        let from = match from {
+            Some(s) => parse_bound(s)?,
+            None => DateTime::UNIX_EPOCH,
+        };
+        let to = match to {
+            Some(s) => parse_bound(s)?,
+            None => NaiveDate::from_ymd_opt(9999, 12, 31)
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+                .map(|dt| dt.and_utc())
+                .unwrap_or(DateTime::<Utc>::MAX_UTC),
+        };
+        if from >= to {
+            return Err("'from' must be before 'to'".to_string());
+        }
+        Ok(Range { from, to })
+    }
+
+    /// Bounds as stored timestamp strings, for range queries.
+    pub fn bounds(&self) -> (String, String) {
+        (self.from.to_rfc3339(), self.to.to_rfc3339())
+    }
+}
+
+fn parse_bound(s: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(ts) = DateTime::parse_from_rfc3339(s) {
+        return Ok(ts.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|dt| dt.and_utc())
+        .ok_or_else(|| format!("invalid date or timestamp '{}'", s))
+}
+
+/// Bar width for resampled history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interval {
+    FifteenMinutes,
+    Hour,
+    Day,
+    Week,
+    Month,
+}
+
+impl FromStr for Interval {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "15m" => Ok(Interval::FifteenMinutes),
+            "1h" => Ok(Interval::Hour),
+            "1d" => Ok(Interval::Day),
+            "1w" => Ok(Interval::Week),
+            "1mo" => Ok(Interval::Month),
+            _ => Err(format!(
+                "unknown interval '{}' (expected 15m, 1h, 1d, 1w or 1mo)",
+                s
+            )),
+        }
+    }
+}
+
+/// Where a point's bar starts: a UTC instant for intraday intervals, or the
+/// first session date of the bar on `exchange` for daily and longer ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Bucket {
+    Instant(DateTime<Utc>),
+    Sessions(NaiveDate),
+}
+
+impl Interval {
+    fn bucket(self, ts: DateTime<Utc>, exchange: &Exchange) -> Bucket {
+        let floor = |secs: i64| {
+            let t = ts.timestamp();
+            DateTime::from_timestamp(t - t.rem_euclid(secs), 0).unwrap_or(ts)
+        };
+        match self {
+            Interval::FifteenMinutes => Bucket::Instant(floor(15 * 60)),
+            Interval::Hour => Bucket::Instant(floor(60 * 60)),
+            Interval::Day => Bucket::Sessions(exchange.trading_date(ts)),
+            Interval::Week => {
+                let date = exchange.trading_date(ts);
+                let monday = date - Duration::days(date.weekday().num_days_from_monday() as i64);
+                Bucket::Sessions(monday)
+            }
+            Interval::Month => {
+                let date = exchange.trading_date(ts);
+                Bucket::Sessions(date.with_day(1).unwrap_or(date))
+            }
+        }
+    }
+}
+
+/// Fold stored points (oldest first) into OHLC bars.
+///
+/// Intraday bars are stamped at their UTC start. Daily, weekly and monthly
+/// bars follow `exchange`'s sessions and are stamped at the open of the first
+/// session that has data. Stored daily bars keep their own open, high and low.
+pub fn resample(points: &[OhlcPoint], interval: Interval, exchange: &Exchange) -> Vec<OhlcBar> {
+    let mut bars: Vec<OhlcBar> = Vec::new();
+    let mut current: Option<Bucket> = None;
+
+    for p in points {
+        let Ok(ts) = DateTime::parse_from_rfc3339(&p.timestamp) else {
+            continue;
+        };
+        let ts = ts.with_timezone(&Utc);
+        let bucket = interval.bucket(ts, exchange);
+        let high = p.high.unwrap_or(p.close);
+        let low = p.low.unwrap_or(p.close);
+
+        match bars.last_mut() {
+            Some(bar) if current == Some(bucket) => {
+                bar.high = bar.high.max(high);
+                bar.low = bar.low.min(low);
+                bar.close = p.close;
+                bar.closed_at = p.timestamp.clone();
+                bar.volume = match (bar.volume, p.volume) {
+                    (Some(a), Some(b)) => Some(a + b),
+                    (a, b) => a.or(b),
+                };
+            }
+            _ => {
+                let start = match bucket {
+                    Bucket::Instant(t) => t,
+                    Bucket::Sessions(_) => exchange.session_open(exchange.trading_date(ts)),
+                };
+                bars.push(OhlcBar {
+                    timestamp: start.to_rfc3339(),
+                    closed_at: p.timestamp.clone(),
+                    open: p.open.unwrap_or(p.close),
+                    high,
+                    low,
+                    close: p.close,
+                    volume: p.volume,
+                });
+                current = Some(bucket);
+            }
+        }
+    }
+
+    bars
+}
@@ -0,0 +1,84 @@
+//! Conditional GET for data routes.
+//!
+//! Data only changes when the fetcher commits a run, so responses are
+//! validated against the latest-state cache: its fingerprint is the ETag and
+//! its newest snapshot or quote is Last-Modified. Requests whose
+//! `If-None-Match` or `If-Modified-Since` matches get a bodiless 304 without
+//! running the handler. Only successful responses carry the validators, so a
+//! client only holds them for URLs that have answered.
+
+use crate::routes::AppState;
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, Utc};
+
+/// Browsers revalidate after a minute; shared caches may serve a stale copy
+/// for a while longer while they revalidate in the background.
+const CACHE_CONTROL: &str = "public, max-age=60, stale-while-revalidate=300";
+
+pub async fn conditional(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let latest = state.cache.load();
+    if latest.etag.is_empty() {
+        return next.run(req).await;
+    }
+
+    let mut validators = HeaderMap::new();
+    validators.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static(CACHE_CONTROL),
+    );
+    if let Ok(etag) = HeaderValue::from_str(&latest.etag) {
+        validators.insert(header::ETAG, etag);
+    }
+    if let Some(modified) = latest.last_modified {
+        if let Ok(value) = HeaderValue::from_str(&http_date(modified)) {
+            validators.insert(header::LAST_MODIFIED, value);
+        }
+    }
+
+    if not_modified(req.headers(), &latest.etag, latest.last_modified) {
+        return (StatusCode::NOT_MODIFIED, validators).into_response();
+    }
+    let mut response = next.run(req).await;
+    if !response.status().is_success() {
+        return response;
+    }
+    response.headers_mut().extend(validators);
+    response
+}
+
+/// Whether the client's copy is current. `If-None-Match` wins when both
+/// conditions are sent, as RFC 9110 requires.
+fn not_modified(headers: &HeaderMap, etag: &str, modified: Option<DateTime<Utc>>) -> bool {
+    if let Some(tags) = headers.get(header::IF_NONE_MATCH) {
+        let tags = tags.to_str().unwrap_or("");
+        return tags
+            .split(',')
+            .map(|t| t.trim().trim_start_matches("W/"))
+            .any(|t| t == "*" || t == etag);
+    }
+    let since = headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| DateTime::parse_from_rfc2822(v).ok());
+    match (since, modified) {
+        // HTTP dates have whole-second precision.
+        (Some(since), Some(modified)) => modified.timestamp() <= since.timestamp(),
+        _ => false,
+    }
+}
+
+/// Forbid caching, for admin routes.
+pub async fn no_store(mut response: Response) -> Response {
+    response
+        .headers_mut()
+        .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    response
+}
+
+/// Format as an HTTP date, e.g. `Sun, 18 Oct 2026 20:00:00 GMT`.
+pub fn http_date(ts: DateTime<Utc>) -> String {
+    ts.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
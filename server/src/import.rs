@@ -0,0 +1,484 @@
+//! Bulk import of daily OHLCV bars from vendor CSV files.
+//!
+//! Rows are validated and deduplicated in memory and the bars written in one
+//! transaction. The follow-up writes (base prices, the restated changes
+//! around the imported range and the recomputed index snapshots from the
+//! earliest imported session onward) each commit on their own, so an import
+//! that fails part-way can leave them half done; re-running the same import
+//! is idempotent and completes them.
+//!
+//! Vendor files carry no market caps, so imported bars take the symbol's
+//! latest stored market cap. Historical weights derived from them are an
+//! approximation that assumes today's caps.
+
+use crate::config::{Exchange, StocksConfig};
+use crate::index;
+use crate::models::PriceBar;
+use crate::store::{StoreResult, Stores};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+/// Date formats accepted for date-only columns, tried in order.
+const DATE_FORMATS: [&str; 3] = ["%Y-%m-%d", "%Y/%m/%d", "%m/%d/%Y"];
+
+/// Rejected rows listed in the report before the rest are summarized.
+const MAX_REPORTED_ERRORS: usize = 50;
+
+/// Which CSV header holds each field. Matching is case-insensitive.
+#[derive(Debug, Clone)]
+pub struct ColumnMap {
+    pub symbol: String,
+    pub date: String,
+    pub open: String,
+    pub high: String,
+    pub low: String,
+    pub close: String,
+    pub volume: String,
+}
+
+impl Default for ColumnMap {
+    fn default() -> Self {
+        ColumnMap {
+            symbol: "symbol".to_string(),
+            date: "date".to_string(),
+            open: "open".to_string(),
+            high: "high".to_string(),
+            low: "low".to_string(),
+            close: "close".to_string(),
+            volume: "volume".to_string(),
+        }
+    }
+}
+
+impl ColumnMap {
+    /// Apply a `field=Header` override, e.g. `close=Adj Close`.
+    pub fn set(&mut self, mapping: &str) -> Result<(), ImportError> {
+        let (field, header) = mapping.split_once('=').ok_or_else(|| {
+            ImportError::Invalid(format!("expected field=header, got '{}'", mapping))
+        })?;
+        let slot = match field.trim() {
+            "symbol" => &mut self.symbol,
+            "date" => &mut self.date,
+            "open" => &mut self.open,
+            "high" => &mut self.high,
+            "low" => &mut self.low,
+            "close" => &mut self.close,
+            "volume" => &mut self.volume,
+            other => return Err(ImportError::Invalid(format!("unknown field '{}'", other))),
+        };
+        *slot = header.trim().to_string();
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ImportOptions {
+    pub columns: ColumnMap,
+    /// Symbol for every row, for single-symbol files without a symbol column.
+    pub symbol: Option<String>,
+    /// Validate and report without writing anything.
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    pub rows_read: usize,
+    pub rows_imported: usize,
+    /// Rows replaced by a later row for the same symbol and session.
+    pub duplicates: usize,
+    pub rejected: usize,
+    /// `line N: reason` for the first rejected rows.
+    pub errors: Vec<String>,
+    pub symbols: Vec<String>,
+    pub snapshots_recomputed: usize,
+}
+
+#[derive(Debug)]
+pub enum ImportError {
+    /// Bad options or a header missing a required column.
+    Invalid(String),
+    Csv(csv::Error),
+    Db(sqlx::Error),
+}
+
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImportError::Invalid(msg) => write!(f, "{}", msg),
+            ImportError::Csv(e) => write!(f, "CSV error: {}", e),
+            ImportError::Db(e) => write!(f, "database error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+impl From<csv::Error> for ImportError {
+    fn from(e: csv::Error) -> Self {
+        ImportError::Csv(e)
+    }
+}
+
+impl From<sqlx::Error> for ImportError {
+    fn from(e: sqlx::Error) -> Self {
+        ImportError::Db(e)
+    }
+}
+
+/// Column positions resolved against a file's header row.
+struct Layout {
+    symbol: Option<usize>,
+    date: usize,
+    open: Option<usize>,
+    high: Option<usize>,
+    low: Option<usize>,
+    close: usize,
+    volume: Option<usize>,
+}
+
+impl Layout {
+    fn resolve(headers: &csv::StringRecord, opts: &ImportOptions) -> Result<Self, ImportError> {
+        let find = |name: &str| {
+            headers
+                .iter()
+                .position(|h| h.trim().eq_ignore_ascii_case(name))
+        };
+        let require = |name: &str| {
+            find(name).ok_or_else(|| ImportError::Invalid(format!("missing column '{}'", name)))
+        };
+        let c = &opts.columns;
+        Ok(Layout {
+            symbol: match opts.symbol {
+                Some(_) => None,
+                None => Some(require(&c.symbol)?),
+            },
+            date: require(&c.date)?,
+            open: find(&c.open),
+            high: find(&c.high),
+            low: find(&c.low),
+            close: require(&c.close)?,
+            volume: find(&c.volume),
+        })
+    }
+}
+
+/// Load bars from `reader`, then recompute affected snapshots.
+pub async fn import_csv<R: std::io::Read>(
+    stores: &Stores,
+    config: &StocksConfig,
+    reader: R,
+    opts: &ImportOptions,
+) -> Result<ImportReport, ImportError> {
+    let mut csv = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+    let layout = Layout::resolve(csv.headers()?, opts)?;
+    let known: Vec<String> = config.all_symbols();
+    let now = Utc::now();
+
+    let mut report = ImportReport::default();
+    let mut bars: BTreeMap<(String, String), PriceBar> = BTreeMap::new();
+
+    for (i, record) in csv.records().enumerate() {
+        // Line 1 is the header.
+        let line = i + 2;
+        report.rows_read += 1;
+        let parsed = record
+            .map_err(|e| e.to_string())
+            .and_then(|r| parse_row(&r, &layout, opts, config, &known, now));
+        match parsed {
+            Ok(bar) => {
+                let key = (bar.symbol.clone(), bar.timestamp.clone());
+                if bars.insert(key, bar).is_some() {
+                    report.duplicates += 1;
+                }
+            }
+            Err(reason) => {
+                report.rejected += 1;
+                if report.errors.len() < MAX_REPORTED_ERRORS {
+                    report.errors.push(format!("line {}: {}", line, reason));
+                }
+            }
+        }
+    }
+    if report.rejected > report.errors.len() {
+        report.errors.push(format!(
+            "... and {} more rejected rows",
+            report.rejected - report.errors.len()
+        ));
+    }
+
+    // Group by symbol, oldest first.
+    let mut by_symbol: HashMap<String, Vec<PriceBar>> = HashMap::new();
+    for ((symbol, _), bar) in bars {
+        by_symbol.entry(symbol).or_default().push(bar);
+    }
+    let mut earliest: Option<NaiveDate> = None;
+    let mut all: Vec<PriceBar> = Vec::new();
+    for (symbol, mut rows) in by_symbol {
+        // An approximation (see the module docs): a NULL cap would weight the
+        // symbol as if it were worth 1.0 next to its peers.
+        let market_cap = stores.prices.latest_market_cap(&symbol).await?;
+        for bar in &mut rows {
+            bar.market_cap = market_cap;
+        }
+        if let Some(first) = rows.first() {
+            let date = config
+                .reference_exchange()
+                .trading_date(parse_ts(&first.timestamp));
+            earliest = Some(earliest.map_or(date, |d| d.min(date)));
+        }
+        report.symbols.push(symbol);
+        all.extend(rows);
+    }
+    report.symbols.sort();
+    report.rows_imported = all.len();
+
+    if opts.dry_run || all.is_empty() {
+        return Ok(report);
+    }
+
+    stores.prices.upsert_bars(&all).await?;
+    for symbol in &report.symbols {
+        let mut imported = all.iter().filter(|b| &b.symbol == symbol);
+        let Some(first) = imported.next() else {
+            continue;
+        };
+        let last = imported.next_back().unwrap_or(first);
+        // The earliest bar seeds the base price on a fresh deployment.
+        stores
+            .prices
+            .insert_base_price(symbol, first.close, &first.timestamp)
+            .await?;
+        restate_changes(
+            stores,
+            config.exchange_for(symbol),
+            symbol,
+            parse_ts(&first.timestamp),
+            parse_ts(&last.timestamp),
+        )
+        .await?;
+    }
+
+    report.snapshots_recomputed = index::compute_history(stores, config, earliest).await?;
+    stores.snapshots.bump_data_version().await?;
+    Ok(report)
+}
+
+/// Recompute the change against the previous session's close for every
+/// stored row from the first imported session through the session after the
+/// last one, so imported bars chain onto the stored close before them and the
+/// stored session after them chains onto the imported close.
+async fn restate_changes(
+    stores: &Stores,
+    exchange: &Exchange,
+    symbol: &str,
+    first: DateTime<Utc>,
+    last: DateTime<Utc>,
+) -> StoreResult<()> {
+    let start = exchange.session_open(exchange.trading_date(first));
+    let mut end = exchange.session_open(exchange.next_session(exchange.trading_date(last)));
+    if let Some(following) = stores.prices.after(symbol, &end.to_rfc3339()).await? {
+        let date = exchange.trading_date(parse_ts(&following.timestamp));
+        end = exchange.session_open(exchange.next_session(date));
+    }
+
+    let mut prev_close = stores
+        .prices
+        .before(symbol, &start.to_rfc3339())
+        .await?
+        .map(|r| r.price);
+    let rows = stores
+        .prices
+        .range(symbol, &start.to_rfc3339(), &end.to_rfc3339())
+        .await?;
+
+    let mut session: Option<NaiveDate> = None;
+    let mut session_close = prev_close;
+    for mut row in rows {
+        let date = exchange.trading_date(parse_ts(&row.timestamp));
+        if session != Some(date) {
+            session = Some(date);
+            prev_close = session_close;
+        }
+        session_close = Some(row.price);
+
+        let (change, change_pct) = match prev_close.filter(|p| *p > 0.0) {
+            Some(prev) => (
+                Some(row.price - prev),
+                Some((row.price - prev) / prev * 100.0),
+            ),
+            None => (None, None),
+        };
+        if (change, change_pct) != (row.change, row.change_pct) {
+            row.change = change;
+            row.change_pct = change_pct;
+            stores.prices.upsert(&row).await?;
+        }
+    }
+    Ok(())
+}
+
+fn parse_row(
+    record: &csv::StringRecord,
+    layout: &Layout,
+    opts: &ImportOptions,
+    config: &StocksConfig,
+    known: &[String],
+    now: DateTime<Utc>,
+) -> Result<PriceBar, String> {
+    let field = |idx: usize| record.get(idx).unwrap_or("");
+
+    let symbol = match (&opts.symbol, layout.symbol) {
+        (Some(s), _) => s.to_uppercase(),
+        (None, Some(idx)) => field(idx).to_uppercase(),
+        (None, None) => unreachable!("layout requires a symbol column"),
+    };
+    if symbol.is_empty() {
+        return Err("empty symbol".to_string());
+    }
+    if !known.contains(&symbol) {
+        return Err(format!("{} is not in stocks.toml", symbol));
+    }
+
+    let exchange = config.exchange_for(&symbol);
+    let raw_date = field(layout.date);
+    let timestamp = match DateTime::parse_from_rfc3339(raw_date) {
+        Ok(ts) => ts.with_timezone(&Utc),
+        Err(_) => {
+            let date = DATE_FORMATS
+                .iter()
+                .find_map(|f| NaiveDate::parse_from_str(raw_date, f).ok())
+                .ok_or_else(|| format!("invalid date '{}'", raw_date))?;
+            if !exchange.is_session_day(date) {
+                return Err(format!("{} is not a trading day", date));
+            }
+            // Daily bars are stored at the session close, like backfill.
+            exchange.session_close(date)
+        }
+    };
+    if timestamp > now {
+        return Err(format!("{} is in the future", raw_date));
+    }
+
+    let number = |idx: Option<usize>, name: &str| -> Result<Option<f64>, String> {
+        let raw = match idx.map(field) {
+            None | Some("") => return Ok(None),
+            Some(raw) => raw,
+        };
+        match raw.replace(',', "").parse::<f64>() {
+            Ok(v) if v.is_finite() && v >= 0.0 => Ok(Some(v)),
+            _ => Err(format!("invalid {} '{}'", name, raw)),
+        }
+    };
+    let close = number(Some(layout.close), "close")?
+        .filter(|c| *c > 0.0)
+        .ok_or_else(|| "close must be a positive number".to_string())?;
+    let open = number(layout.open, "open")?;
+    let high = number(layout.high, "high")?;
+    let low = number(layout.low, "low")?;
+    let volume = number(layout.volume, "volume")?;
+
+    if let Some(high) = high {
+        if [open, low, Some(close)]
+            .into_iter()
+            .flatten()
+            .any(|v| v > high)
+        {
+            return Err(format!("high {} is below open, low or close", high));
+        }
+    }
+    if let Some(low) = low {
+        if [open, Some(close)].into_iter().flatten().any(|v| v < low) {
+            return Err(format!("low {} is above open or close", low));
+        }
+    }
+
+    Ok(PriceBar {
+        symbol,
+        timestamp: timestamp.to_rfc3339(),
+        open,
+        high,
+        low,
+        close,
+        volume,
+        change: None,
+        change_pct: None,
+        market_cap: None,
+    })
+}
+
+fn parse_ts(ts: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(ts)
+        .map(|t| t.with_timezone(&Utc))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::PriceRecord;
+
+    const CONFIG: &str = r#"
+[settings]
+base_value = 1000.0
+market_cap_weight_pct = 50
+
+[sectors.chips]
+label = "Chips"
+symbols = ["AAA"]
+
+[benchmarks]
+symbols = ["SPY"]
+"#;
+
+    fn price(price: f64, change: Option<f64>, timestamp: &str) -> PriceRecord {
+        PriceRecord {
+            symbol: "AAA".to_string(),
+            price,
+            change,
+            change_pct: None,
+            market_cap: Some(1e9),
+            timestamp: timestamp.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn imported_bars_chain_onto_stored_closes() {
+        let config = StocksConfig::parse("test".to_string(), CONFIG).unwrap();
+        let stores = Stores::memory();
+        for row in [
+            price(100.0, None, "2024-03-07T21:00:00+00:00"),
+            price(118.0, Some(3.0), "2024-03-12T15:00:00+00:00"),
+            price(120.0, Some(5.0), "2024-03-12T20:00:00+00:00"),
+        ] {
+            stores.prices.upsert(&row).await.unwrap();
+        }
+
+        let csv = "symbol,date,close\nAAA,2024-03-08,105\nAAA,2024-03-11,110\n";
+        let report = import_csv(&stores, &config, csv.as_bytes(), &ImportOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(report.rows_imported, 2);
+
+        let changes: Vec<(f64, Option<f64>)> = stores
+            .prices
+            .series("AAA")
+            .await
+            .unwrap()
+            .iter()
+            .map(|r| (r.price, r.change))
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                (100.0, None),
+                (105.0, Some(5.0)),
+                (110.0, Some(5.0)),
+                (118.0, Some(8.0)),
+                (120.0, Some(10.0)),
+            ]
+        );
+    }
+}
@@ -1,112 +1,561 @@
 use crate::config::StocksConfig;
-use crate::models::IndexSnapshot;
-use chrono::Utc;
-use sqlx::SqlitePool;
-use std::sync::Arc;
+use crate::models::{IndexSnapshot, PriceRecord, WeightPoint};
+use crate::store::{StoreResult, Stores};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use std::collections::{BTreeSet, HashMap};
 
-/// Compute the hybrid-weighted index value and store a snapshot.
+/// Compute the hybrid-weighted index value from the latest committed prices
+/// and store a snapshot stamped with the fetch cycle's timestamp.
 pub async fn compute_and_store(
-    pool: &SqlitePool,
-    config: &Arc<StocksConfig>,
-) -> Option<IndexSnapshot> {
+    stores: &Stores,
+    config: &StocksConfig,
+    cycle: &str,
+) -> StoreResult<Option<IndexSnapshot>> {
     let index_symbols = config.index_symbols();
     let mcap_pct = config.mcap_pct();
     let base_value = config.settings.base_value;
-    let n = index_symbols.len() as f64;
 
-    if n == 0.0 {
-        return None;
+    if index_symbols.is_empty() {
+        return Ok(None);
     }
 
     // Gather latest price + base price + market cap for each index symbol.
     let mut entries: Vec<(f64, f64, f64)> = Vec::new(); // (current, base, mcap)
 
     for sym in &index_symbols {
-        let latest = sqlx::query_as::<_, (f64, Option<f64>)>(
-            "SELECT price, market_cap FROM prices
-             WHERE symbol = ? ORDER BY timestamp DESC LIMIT 1",
-        )
-        .bind(sym)
-        .fetch_optional(pool)
-        .await
-        .ok()
-        .flatten();
-
-        let base = sqlx::query_as::<_, (f64,)>("SELECT price FROM base_prices WHERE symbol = ?")
-            .bind(sym)
-            .fetch_optional(pool)
-            .await
-            .ok()
-            .flatten();
+        let latest = stores.prices.latest(sym).await?;
+        let base = stores.prices.base_price(sym).await?;
 
-        if let (Some((current_price, mcap_opt)), Some((base_price,))) = (latest, base) {
-            if base_price > 0.0 && current_price > 0.0 {
-                let mcap = mcap_opt.unwrap_or(1.0); // fallback equal
-                entries.push((current_price, base_price, mcap));
+        if let (Some(latest), Some(base_price)) = (latest, base) {
+            if base_price > 0.0 && latest.price > 0.0 {
+                let mcap = latest.market_cap.unwrap_or(1.0); // fallback equal
+                entries.push((latest.price, base_price, mcap));
             }
         }
     }
 
     if entries.is_empty() {
-        return None;
+        return Ok(None);
     }
 
-    let total_mcap: f64 = entries.iter().map(|(_, _, m)| m).sum();
-    let equal_weight = 1.0 / entries.len() as f64;
+    let index_value = blend(&entries, mcap_pct, base_value);
+
+    // Compute daily change from previous snapshot.
+    let prev = stores.snapshots.latest().await?;
+    let (daily_change, daily_change_pct) = change_from(prev.map(|s| s.value), index_value);
+
+    let snapshot = IndexSnapshot {
+        value: index_value,
+        daily_change,
+        daily_change_pct,
+        timestamp: cycle.to_string(),
+    };
+    stores.snapshots.upsert(&snapshot).await?;
+
+    tracing::info!("Index computed: {:.2}", index_value);
+    Ok(Some(snapshot))
+}
+
+/// (timestamp, price, market_cap) points for one symbol, ascending.
+type Series = Vec<(DateTime<Utc>, f64, Option<f64>)>;
+
+/// Compute one snapshot per reference-exchange session from stored prices.
+///
+/// Each snapshot is stamped at the reference session close, and every
+/// constituent contributes its last price at or before that instant, so
+/// venues that close earlier (or later, on the next local date) line up with
+/// the session they actually traded in. With `since`, sessions before that
+/// date are only used to seed the daily change. Returns the number of
+/// snapshots written.
+pub async fn compute_history(
+    stores: &Stores,
+    config: &StocksConfig,
+    since: Option<NaiveDate>,
+) -> StoreResult<usize> {
+    let index_symbols = config.index_symbols();
+    let mcap_pct = config.mcap_pct();
+    let base_value = config.settings.base_value;
+    let reference = config.reference_exchange();
+
+    // (price series, base price) per symbol.
+    let mut series: Vec<(Series, f64)> = Vec::new();
+    let mut dates: BTreeSet<NaiveDate> = BTreeSet::new();
 
-    let mut index_value = 0.0;
-    for (current, base, mcap) in &entries {
-        let mcap_weight = if total_mcap > 0.0 {
-            mcap / total_mcap
-        } else {
-            equal_weight
+    for sym in &index_symbols {
+        let base_price = match stores.prices.base_price(sym).await? {
+            Some(p) if p > 0.0 => p,
+            _ => continue,
         };
-        let blended = (mcap_pct * mcap_weight) + ((1.0 - mcap_pct) * equal_weight);
-        index_value += blended * (current / base);
+
+        let points = load_series(stores, sym).await?;
+
+        dates.extend(points.iter().map(|(ts, _, _)| reference.trading_date(*ts)));
+        series.push((points, base_price));
     }
-    index_value *= base_value;
 
-    // Compute daily change from previous snapshot.
-    let prev = sqlx::query_as::<_, (f64,)>(
-        "SELECT value FROM index_snapshots ORDER BY timestamp DESC LIMIT 1",
-    )
-    .fetch_optional(pool)
-    .await
-    .ok()
-    .flatten();
-
-    let (daily_change, daily_change_pct) = if let Some((prev_val,)) = prev {
-        if prev_val > 0.0 {
-            let chg = index_value - prev_val;
-            let pct = (chg / prev_val) * 100.0;
-            (Some(chg), Some(pct))
-        } else {
-            (None, None)
+    let mut prev: Option<f64> = None;
+    let mut written = 0;
+
+    for date in &dates {
+        let close = reference.session_close(*date);
+        if close > Utc::now() {
+            // The live loop snapshots the session still in progress.
+            continue;
         }
-    } else {
-        (None, None)
+
+        let entries: Vec<(f64, f64, f64)> = series
+            .iter()
+            .filter_map(|(points, base)| {
+                let idx = points.partition_point(|(ts, _, _)| *ts <= close);
+                let (_, price, mcap) = points.get(idx.checked_sub(1)?)?;
+                Some((*price, *base, mcap.unwrap_or(1.0)))
+            })
+            .collect();
+
+        if entries.is_empty() {
+            continue;
+        }
+
+        let index_value = blend(&entries, mcap_pct, base_value);
+        let (daily_change, daily_change_pct) = change_from(prev, index_value);
+        prev = Some(index_value);
+        if since.is_some_and(|since| *date < since) {
+            continue;
+        }
+
+        stores
+            .snapshots
+            .upsert(&IndexSnapshot {
+                value: index_value,
+                daily_change,
+                daily_change_pct,
+                timestamp: close.to_rfc3339(),
+            })
+            .await?;
+        written += 1;
+    }
+
+    Ok(written)
+}
+
+/// Blended constituent weights at each reference session close in
+/// `[from, to)`, using the market caps the index saw at that close.
+pub async fn weights_history(
+    stores: &Stores,
+    config: &StocksConfig,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> StoreResult<Vec<WeightPoint>> {
+    let mcap_pct = config.mcap_pct();
+    let reference = config.reference_exchange();
+
+    let series = constituents(stores, config, from, to).await?;
+    let dates: BTreeSet<NaiveDate> = series
+        .iter()
+        .flat_map(|(_, points)| points.iter().map(|(ts, _, _)| reference.trading_date(*ts)))
+        .collect();
+
+    let mut out = Vec::new();
+    for date in &dates {
+        let close = reference.session_close(*date);
+        if close < from || close >= to || close > Utc::now() {
+            continue;
+        }
+
+        let timestamp = close.to_rfc3339();
+        for (sym, market_cap, weight) in weights_at(&series, close, mcap_pct) {
+            out.push(WeightPoint {
+                timestamp: timestamp.clone(),
+                symbol: sym.to_string(),
+                market_cap,
+                weight,
+            });
+        }
+    }
+
+    Ok(out)
+}
+
+/// A symbol's blended weight at each of `instants`, or `None` where it had
+/// not yet traded (or is not an index constituent).
+pub async fn symbol_weights(
+    stores: &Stores,
+    config: &StocksConfig,
+    symbol: &str,
+    instants: &[DateTime<Utc>],
+) -> StoreResult<Vec<Option<f64>>> {
+    let mcap_pct = config.mcap_pct();
+    let (Some(first), Some(last)) = (instants.iter().min(), instants.iter().max()) else {
+        return Ok(Vec::new());
     };
+    let series = constituents(stores, config, *first, *last + Duration::microseconds(1)).await?;
+    if !series.iter().any(|(sym, _)| sym == symbol) {
+        return Ok(vec![None; instants.len()]);
+    }
 
-    let now = Utc::now().to_rfc3339();
+    Ok(instants
+        .iter()
+        .map(|at| {
+            weights_at(&series, *at, mcap_pct)
+                .into_iter()
+                .find(|(sym, _, _)| *sym == symbol)
+                .map(|(_, _, weight)| weight)
+        })
+        .collect())
+}
 
-    let _ = sqlx::query(
-        "INSERT INTO index_snapshots (value, daily_change, daily_change_pct, timestamp)
-         VALUES (?, ?, ?, ?)",
-    )
-    .bind(index_value)
-    .bind(daily_change)
-    .bind(daily_change_pct)
-    .bind(&now)
-    .execute(pool)
-    .await;
+/// Price series in `[from, to)` of every index constituent that has a base
+/// price, each led by its last price before `from` so weights at `from`
+/// still see constituents that last traded earlier.
+async fn constituents(
+    stores: &Stores,
+    config: &StocksConfig,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> StoreResult<Vec<(String, Series)>> {
+    let (from, to) = (from.to_rfc3339(), to.to_rfc3339());
+    let mut series = Vec::new();
+    for sym in config.index_symbols() {
+        if !matches!(stores.prices.base_price(&sym).await?, Some(p) if p > 0.0) {
+            continue;
+        }
+        let prior = stores.prices.before(&sym, &from).await?;
+        let rows = stores.prices.range(&sym, &from, &to).await?;
+        series.push((sym, to_series(prior.into_iter().chain(rows))));
+    }
+    Ok(series)
+}
 
-    let snapshot = IndexSnapshot {
-        value: index_value,
-        daily_change,
-        daily_change_pct,
-        timestamp: now,
+/// (symbol, market cap, weight) for constituents that had traded by `at`,
+/// using the market cap recorded with their last price at or before it.
+fn weights_at(
+    series: &[(String, Series)],
+    at: DateTime<Utc>,
+    mcap_pct: f64,
+) -> Vec<(&str, Option<f64>, f64)> {
+    let present: Vec<(&str, Option<f64>)> = series
+        .iter()
+        .filter_map(|(sym, points)| {
+            let idx = points.partition_point(|(ts, _, _)| *ts <= at);
+            let (_, _, mcap) = points.get(idx.checked_sub(1)?)?;
+            Some((sym.as_str(), *mcap))
+        })
+        .collect();
+    let mcaps: Vec<f64> = present.iter().map(|(_, m)| m.unwrap_or(1.0)).collect();
+
+    present
+        .into_iter()
+        .zip(blended_weights(&mcaps, mcap_pct))
+        .map(|((sym, mcap), weight)| (sym, mcap, weight))
+        .collect()
+}
+
+/// Compute blended weights for all index symbols from their latest prices.
+pub fn weights(
+    config: &StocksConfig,
+    latest: &HashMap<String, PriceRecord>,
+) -> HashMap<String, f64> {
+    let index_symbols = config.index_symbols();
+
+    let mcaps: Vec<f64> = index_symbols
+        .iter()
+        .map(|sym| latest.get(sym).and_then(|r| r.market_cap).unwrap_or(1.0))
+        .collect();
+
+    index_symbols
+        .into_iter()
+        .zip(blended_weights(&mcaps, config.mcap_pct()))
+        .collect()
+}
+
+/// Blend market-cap and equal weighting for a set of constituents.
+fn blended_weights(mcaps: &[f64], mcap_pct: f64) -> Vec<f64> {
+    let total_mcap: f64 = mcaps.iter().sum();
+    let equal_weight = if mcaps.is_empty() {
+        0.0
+    } else {
+        1.0 / mcaps.len() as f64
     };
 
-    tracing::info!("Index computed: {:.2}", index_value);
-    Some(snapshot)
+    mcaps
+        .iter()
+        .map(|mcap| {
+            let mcap_weight = if total_mcap > 0.0 {
+                mcap / total_mcap
+            } else {
+                equal_weight
+            };
+            (mcap_pct * mcap_weight) + ((1.0 - mcap_pct) * equal_weight)
+        })
+        .collect()
+}
+
+/// Blend (current, base, market_cap) entries into an index value.
+fn blend(entries: &[(f64, f64, f64)], mcap_pct: f64, base_value: f64) -> f64 {
+    let mcaps: Vec<f64> = entries.iter().map(|(_, _, m)| *m).collect();
+    let weights = blended_weights(&mcaps, mcap_pct);
+
+    let index_value: f64 = entries
+        .iter()
+        .zip(weights)
+        .map(|((current, base, _), w)| w * (current / base))
+        .sum();
+    index_value * base_value
+}
+
+/// A symbol's stored prices as a positive-price series, oldest first.
+async fn load_series(stores: &Stores, symbol: &str) -> StoreResult<Series> {
+    Ok(to_series(stores.prices.series(symbol).await?))
+}
+
+/// Positive-price points from stored rows, keeping their order.
+fn to_series(rows: impl IntoIterator<Item = PriceRecord>) -> Series {
+    rows.into_iter()
+        .filter(|r| r.price > 0.0)
+        .filter_map(|r| {
+            let ts = DateTime::parse_from_rfc3339(&r.timestamp)
+                .ok()?
+                .with_timezone(&Utc);
+            Some((ts, r.price, r.market_cap))
+        })
+        .collect()
+}
+
+/// Absolute and percent change of `value` against a previous value.
+fn change_from(prev: Option<f64>, value: f64) -> (Option<f64>, Option<f64>) {
+    match prev {
+        Some(prev_val) if prev_val > 0.0 => {
+            let chg = value - prev_val;
+            let pct = (chg / prev_val) * 100.0;
+            (Some(chg), Some(pct))
+        }
+        _ => (None, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::StocksConfig;
+
+    const CONFIG: &str = r#"
+[settings]
+base_value = 1000.0
+market_cap_weight_pct = 50
+
+[sectors.chips]
+label = "Chips"
+symbols = ["AAA", "BBB"]
+
+[benchmarks]
+symbols = ["SPY"]
+"#;
+
+    fn config() -> StocksConfig {
+        StocksConfig::parse("test".to_string(), CONFIG).unwrap()
+    }
+
+    fn quote(symbol: &str, price: f64, market_cap: f64, timestamp: &str) -> PriceRecord {
+        PriceRecord {
+            symbol: symbol.to_string(),
+            price,
+            change: None,
+            change_pct: None,
+            market_cap: Some(market_cap),
+            timestamp: timestamp.to_string(),
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    // With market caps 3:1 and a 50% blend, AAA weighs 0.625 and BBB 0.375,
+    // so AAA up 10% and BBB flat puts the index at 1062.5.
+
+    #[tokio::test]
+    async fn compute_and_store_blends_and_chains_the_change() {
+        let stores = Stores::memory();
+        let config = config();
+        let first = "2024-03-11T14:00:00+00:00";
+        stores
+            .prices
+            .record_cycle(&[
+                quote("AAA", 100.0, 3.0, first),
+                quote("BBB", 50.0, 1.0, first),
+            ])
+            .await
+            .unwrap();
+        let snapshot = compute_and_store(&stores, &config, first)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_close(snapshot.value, 1000.0);
+        assert_eq!(snapshot.daily_change, None);
+
+        let second = "2024-03-11T14:15:00+00:00";
+        stores
+            .prices
+            .record_cycle(&[
+                quote("AAA", 110.0, 3.0, second),
+                quote("BBB", 50.0, 1.0, second),
+            ])
+            .await
+            .unwrap();
+        let snapshot = compute_and_store(&stores, &config, second)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_close(snapshot.value, 1062.5);
+        assert_close(snapshot.daily_change.unwrap(), 62.5);
+        assert_close(snapshot.daily_change_pct.unwrap(), 6.25);
+        assert_eq!(snapshot.timestamp, second);
+        assert_eq!(stores.snapshots.history(10).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn compute_and_store_needs_a_base_price() {
+        let stores = Stores::memory();
+        stores
+            .prices
+            .upsert(&quote("AAA", 100.0, 3.0, "2024-03-11T14:00:00+00:00"))
+            .await
+            .unwrap();
+        let snapshot = compute_and_store(&stores, &config(), "2024-03-11T14:00:00+00:00")
+            .await
+            .unwrap();
+        assert!(snapshot.is_none());
+    }
+
+    #[tokio::test]
+    async fn compute_history_snapshots_each_session_close() {
+        let stores = Stores::memory();
+        let config = config();
+        // Friday 2024-03-08 closes at 21:00 UTC; Monday 2024-03-11, after the
+        // DST change, at 20:00 UTC.
+        let friday = "2024-03-08T20:00:00+00:00";
+        stores
+            .prices
+            .record_cycle(&[
+                quote("AAA", 100.0, 3.0, friday),
+                quote("BBB", 50.0, 1.0, friday),
+            ])
+            .await
+            .unwrap();
+        // BBB does not trade on Monday and carries its Friday price.
+        stores
+            .prices
+            .upsert(&quote("AAA", 110.0, 3.0, "2024-03-11T19:00:00+00:00"))
+            .await
+            .unwrap();
+        // A print after Monday's close belongs to Monday but not to its snapshot.
+        stores
+            .prices
+            .upsert(&quote("AAA", 150.0, 3.0, "2024-03-11T20:30:00+00:00"))
+            .await
+            .unwrap();
+
+        let written = compute_history(&stores, &config, None).await.unwrap();
+        assert_eq!(written, 2);
+        let history = stores.snapshots.history(10).await.unwrap();
+        let stamps: Vec<&str> = history.iter().map(|s| s.timestamp.as_str()).collect();
+        assert_eq!(
+            stamps,
+            ["2024-03-11T20:00:00+00:00", "2024-03-08T21:00:00+00:00"]
+        );
+        assert_close(history[0].value, 1062.5);
+        assert_close(history[0].daily_change.unwrap(), 62.5);
+        assert_close(history[1].value, 1000.0);
+        assert_eq!(history[1].daily_change, None);
+    }
+
+    #[tokio::test]
+    async fn compute_history_since_only_rewrites_later_sessions() {
+        let stores = Stores::memory();
+        let config = config();
+        let friday = "2024-03-08T20:00:00+00:00";
+        stores
+            .prices
+            .record_cycle(&[
+                quote("AAA", 100.0, 3.0, friday),
+                quote("BBB", 50.0, 1.0, friday),
+            ])
+            .await
+            .unwrap();
+        stores
+            .prices
+            .upsert(&quote("AAA", 110.0, 3.0, "2024-03-11T19:00:00+00:00"))
+            .await
+            .unwrap();
+
+        let since = NaiveDate::from_ymd_opt(2024, 3, 11);
+        let written = compute_history(&stores, &config, since).await.unwrap();
+        assert_eq!(written, 1);
+        let history = stores.snapshots.history(10).await.unwrap();
+        assert_eq!(history.len(), 1);
+        // The change is still measured against Friday.
+        assert_close(history[0].daily_change.unwrap(), 62.5);
+    }
+
+    #[tokio::test]
+    async fn symbol_weights_see_prices_before_the_window() {
+        let stores = Stores::memory();
+        let config = config();
+        let friday = "2024-03-08T20:00:00+00:00";
+        stores
+            .prices
+            .record_cycle(&[
+                quote("AAA", 100.0, 3.0, friday),
+                quote("BBB", 50.0, 1.0, friday),
+            ])
+            .await
+            .unwrap();
+        stores
+            .prices
+            .upsert(&quote("AAA", 110.0, 1.0, "2024-03-11T19:00:00+00:00"))
+            .await
+            .unwrap();
+
+        let at = |ts: &str| {
+            DateTime::parse_from_rfc3339(ts)
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        let instants = [
+            at("2024-03-11T15:00:00+00:00"),
+            at("2024-03-11T19:00:00+00:00"),
+        ];
+        let weights = symbol_weights(&stores, &config, "AAA", &instants)
+            .await
+            .unwrap();
+        assert_close(weights[0].unwrap(), 0.625);
+        assert_close(weights[1].unwrap(), 0.5);
+
+        let early = [at("2024-03-07T20:00:00+00:00")];
+        let weights = symbol_weights(&stores, &config, "AAA", &early)
+            .await
+            .unwrap();
+        assert_eq!(weights, [None]);
+    }
+
+    #[test]
+    fn weights_blend_market_cap_and_equal() {
+        let config = config();
+        let ts = "2024-03-11T14:00:00+00:00";
+        let latest: HashMap<String, PriceRecord> = [
+            ("AAA".to_string(), quote("AAA", 100.0, 3.0, ts)),
+            ("BBB".to_string(), quote("BBB", 50.0, 1.0, ts)),
+        ]
+        .into_iter()
+        .collect();
+        let weights = weights(&config, &latest);
+        assert_close(weights["AAA"], 0.625);
+        assert_close(weights["BBB"], 0.375);
+        assert!(!weights.contains_key("SPY"));
+    }
 }
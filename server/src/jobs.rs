@@ -0,0 +1,92 @@
+//! Admin-triggered background jobs.
+//!
+//! Each job runs on its own task and is tracked in memory so clients can poll
+//! it by ID. Only the most recent jobs are kept, and none survive a restart;
+//! the fetcher runs a job performs are also recorded in `fetch_runs`.
+
+use crate::models::Job;
+use chrono::Utc;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Finished and pending jobs kept for polling.
+const RETAINED: usize = 100;
+
+#[derive(Clone, Default)]
+pub struct Jobs {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    next_id: AtomicU64,
+    jobs: Mutex<VecDeque<Job>>,
+}
+
+impl Jobs {
+    /// Queue `work` as a job of `kind` and return it. The job succeeds with
+    /// the message `work` returns, or fails with its error.
+    pub fn spawn<F>(&self, kind: &str, work: F) -> Job
+    where
+        F: Future<Output = Result<String, String>> + Send + 'static,
+    {
+        let job = Job {
+            id: self.inner.next_id.fetch_add(1, Ordering::Relaxed) + 1,
+            kind: kind.to_string(),
+            status: "queued".to_string(),
+            created_at: Utc::now().to_rfc3339(),
+            started_at: None,
+            finished_at: None,
+            message: None,
+        };
+        {
+            let mut jobs = self.inner.jobs.lock().unwrap();
+            jobs.push_front(job.clone());
+            jobs.truncate(RETAINED);
+        }
+
+        let jobs = self.clone();
+        let id = job.id;
+        tokio::spawn(async move {
+            jobs.update(id, |j| {
+                j.status = "running".to_string();
+                j.started_at = Some(Utc::now().to_rfc3339());
+            });
+            let outcome = work.await;
+            jobs.update(id, |j| {
+                j.finished_at = Some(Utc::now().to_rfc3339());
+                match outcome {
+                    Ok(message) => {
+                        j.status = "succeeded".to_string();
+                        j.message = Some(message);
+                    }
+                    Err(e) => {
+                        tracing::error!("Job {} ({}) failed: {}", j.id, j.kind, e);
+                        j.status = "failed".to_string();
+                        j.message = Some(e);
+                    }
+                }
+            });
+        });
+        job
+    }
+
+    pub fn get(&self, id: u64) -> Option<Job> {
+        let jobs = self.inner.jobs.lock().unwrap();
+        jobs.iter().find(|j| j.id == id).cloned()
+    }
+
+    /// Retained jobs, newest first.
+    pub fn recent(&self) -> Vec<Job> {
+        self.inner.jobs.lock().unwrap().iter().cloned().collect()
+    }
+
+    fn update(&self, id: u64, f: impl FnOnce(&mut Job)) {
+        let mut jobs = self.inner.jobs.lock().unwrap();
+        if let Some(job) = jobs.iter_mut().find(|j| j.id == id) {
+            f(job);
+        }
+    }
+}
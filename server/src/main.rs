@@ -1,69 +1,407 @@
+mod api;
+mod app_config;
+mod auth;
+mod backup;
+mod cache;
+mod calendar;
+mod compaction;
 mod config;
 mod db;
+mod error;
+mod events;
+mod export;
 mod fetcher;
+mod history;
+mod http_cache;
+mod import;
 mod index;
+mod jobs;
+mod metrics;
 mod models;
+mod openapi;
 mod routes;
+mod store;
+mod validate;
 
-use axum::Router;
+use app_config::AppConfig;
+use clap::{Parser, Subcommand};
 use routes::AppState;
+use std::path::PathBuf;
 use std::sync::Arc;
-use tower_http::cors::CorsLayer;
 use tower_http::services::ServeDir;
 
+#[derive(Parser)]
+#[command(version, about = "AI index server")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Settings file (default: `AIINDEX_CONFIG`, if set).
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    /// Address to listen on, overriding the file and `BIND_ADDR`.
+    #[arg(long, global = true)]
+    bind: Option<String>,
+    /// Database URL, overriding the file and `DATABASE_URL`.
+    #[arg(long, global = true)]
+    database_url: Option<String>,
+    /// stocks.toml to use, overriding the file and `AIINDEX_STOCKS_PATH`.
+    #[arg(long, global = true)]
+    stocks: Option<PathBuf>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the API server and background jobs (default).
+    Serve,
+    /// Validate a SQLite backup and swap it in as the database. Stop the server first.
+    Restore { file: PathBuf },
+    /// Export prices, snapshots, weights and profiles to CSV or Parquet files.
+    Export {
+        /// Datasets to export: prices, snapshots, weights, profiles (default: all).
+        #[arg(long = "dataset", value_delimiter = ',')]
+        datasets: Vec<export::Dataset>,
+        #[arg(long, default_value = "csv")]
+        format: export::Format,
+        /// Start of the range (inclusive), as YYYY-MM-DD or RFC 3339.
+        #[arg(long)]
+        from: Option<String>,
+        /// End of the range (exclusive), as YYYY-MM-DD or RFC 3339.
+        #[arg(long)]
+        to: Option<String>,
+        /// Directory to write `<dataset>.<format>` files into.
+        #[arg(long, default_value = ".")]
+        out_dir: PathBuf,
+    },
+    /// Import daily OHLCV bars from a CSV file and recompute index snapshots.
+    Import {
+        file: PathBuf,
+        /// Symbol for every row, for files without a symbol column.
+        #[arg(long)]
+        symbol: Option<String>,
+        /// Map a field to a CSV header, e.g. `--map "close=Adj Close"`. Repeatable.
+        #[arg(long = "map")]
+        mappings: Vec<String>,
+        /// Validate and report without writing anything.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Check stocks.toml and report problems by line. Exits non-zero on errors.
+    ValidateConfig {
+        /// File to check (default: `AIINDEX_STOCKS_PATH` or the built-in config).
+        file: Option<PathBuf>,
+    },
+    /// Mint, list and revoke API keys.
+    Keys {
+        #[command(subcommand)]
+        command: KeysCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum KeysCommand {
+    /// Mint a key and print it. It is not shown again.
+    Create {
+        /// What the key is for, e.g. `ops-laptop`.
+        name: String,
+        /// `read` for the admin GET routes, `admin` for everything.
+        #[arg(long, default_value = "read")]
+        role: auth::Role,
+    },
+    /// List keys, including revoked ones.
+    List,
+    /// Revoke a key by ID.
+    Revoke { id: i64 },
+}
+
 #[tokio::main]
 async fn main() {
+    // Logs go to stderr so commands like `keys create` can print to stdout.
     tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
         .with_env_filter(
             tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()),
         )
         .init();
 
-    let cfg = config::StocksConfig::load().expect("Failed to load stocks.toml");
+    let cli = Cli::parse();
+    let app = match AppConfig::load(app_config::Overrides {
+        config_file: cli.config,
+        bind_addr: cli.bind,
+        database_url: cli.database_url,
+        stocks_path: cli.stocks,
+    }) {
+        Ok(app) => app,
+        Err(e) => {
+            tracing::error!("Invalid settings: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    match cli.command.unwrap_or(Command::Serve) {
+        Command::Serve => serve(app).await,
+        Command::Restore { file } => match backup::restore(&file, &app.database.url).await {
+            Ok(target) => tracing::info!("Restored {} into {}", file.display(), target.display()),
+            Err(e) => {
+                tracing::error!("Restore failed: {}", e);
+                std::process::exit(1);
+            }
+        },
+        Command::Export {
+            datasets,
+            format,
+            from,
+            to,
+            out_dir,
+        } => {
+            if let Err(e) = run_export(&app, datasets, format, from, to, out_dir).await {
+                tracing::error!("Export failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Command::Import {
+            file,
+            symbol,
+            mappings,
+            dry_run,
+        } => {
+            if let Err(e) = run_import(&app, file, symbol, mappings, dry_run).await {
+                tracing::error!("Import failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Command::ValidateConfig { file } => match validate_config(&app, file) {
+            Ok(true) => {}
+            Ok(false) => std::process::exit(1),
+            Err(e) => {
+                tracing::error!("{}", e);
+                std::process::exit(1);
+            }
+        },
+        Command::Keys { command } => {
+            if let Err(e) = run_keys(&app, command).await {
+                tracing::error!("Key command failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+async fn run_import(
+    app: &AppConfig,
+    file: PathBuf,
+    symbol: Option<String>,
+    mappings: Vec<String>,
+    dry_run: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut opts = import::ImportOptions {
+        symbol,
+        dry_run,
+        ..Default::default()
+    };
+    for m in &mappings {
+        opts.columns.set(m)?;
+    }
+    let reader = std::fs::File::open(&file)?;
+    let cfg = config::StocksConfig::load(app.stocks.path.as_deref())?;
+    let pool = db::init_pool(&app.database).await?;
+    let stores = store::Stores::sql(pool);
+
+    let report = import::import_csv(&stores, &cfg, reader, &opts).await?;
+    for err in &report.errors {
+        tracing::warn!("{}", err);
+    }
+    tracing::info!(
+        "{}{} of {} rows imported for {} symbols ({} duplicates, {} rejected), {} snapshots recomputed",
+        if dry_run { "[dry run] " } else { "" },
+        report.rows_imported,
+        report.rows_read,
+        report.symbols.len(),
+        report.duplicates,
+        report.rejected,
+        report.snapshots_recomputed
+    );
+    Ok(())
+}
+
+async fn run_export(
+    app: &AppConfig,
+    mut datasets: Vec<export::Dataset>,
+    format: export::Format,
+    from: Option<String>,
+    to: Option<String>,
+    out_dir: PathBuf,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let range = history::Range::parse(from.as_deref(), to.as_deref())?;
+    if datasets.is_empty() {
+        datasets = export::Dataset::ALL.to_vec();
+    }
+    let cfg = config::StocksConfig::load(app.stocks.path.as_deref())?;
+    let pool = db::init_pool(&app.database).await?;
+    let stores = store::Stores::sql(pool);
+    export::to_files(&stores, &cfg, &datasets, format, range, &out_dir).await?;
+    Ok(())
+}
+
+/// Print every diagnostic for the config. Returns whether it is usable.
+fn validate_config(app: &AppConfig, file: Option<PathBuf>) -> Result<bool, config::ConfigError> {
+    let (origin, text) = match file {
+        Some(path) => {
+            let origin = path.display().to_string();
+            let text = std::fs::read_to_string(&path)
+                .map_err(|e| config::ConfigError::Io(origin.clone(), e))?;
+            (origin, text)
+        }
+        None => config::StocksConfig::source(app.stocks.path.as_deref())?,
+    };
+    let report = validate::check(&text);
+    for d in &report.diagnostics {
+        println!("{}: {}", origin, d);
+    }
+    println!(
+        "{}: {} errors, {} warnings",
+        origin,
+        report.errors().count(),
+        report.warnings().count()
+    );
+    Ok(!report.has_errors())
+}
+
+async fn run_keys(app: &AppConfig, command: KeysCommand) -> Result<(), Box<dyn std::error::Error>> {
+    let pool = db::init_pool(&app.database).await?;
+    let stores = store::Stores::sql(pool);
+    match command {
+        KeysCommand::Create { name, role } => {
+            let (key, secret) = auth::create_key(&stores, &name, role).await?;
+            tracing::info!("Created {} key {} ({})", key.role, key.id, key.name);
+            println!("{}", secret);
+        }
+        KeysCommand::List => {
+            for key in stores.keys.all().await? {
+                let state = match &key.revoked_at {
+                    Some(at) => format!("revoked {}", at),
+                    None => format!(
+                        "last used {}",
+                        key.last_used_at.as_deref().unwrap_or("never")
+                    ),
+                };
+                println!(
+                    "{}\t{}…\t{}\t{}\tcreated {}\t{}",
+                    key.id, key.prefix, key.role, key.name, key.created_at, state
+                );
+            }
+        }
+        KeysCommand::Revoke { id } => {
+            let now = chrono::Utc::now().to_rfc3339();
+            if !stores.keys.revoke(id, &now).await? {
+                return Err(format!("no active key with ID {}", id).into());
+            }
+            tracing::info!("Revoked key {}", id);
+        }
+    }
+    Ok(())
+}
+
+async fn serve(settings: AppConfig) {
+    let settings = Arc::new(settings);
+    let cfg = match config::StocksConfig::load(settings.stocks.path.as_deref()) {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            tracing::error!("Refusing to start: {}", e);
+            std::process::exit(1);
+        }
+    };
     tracing::info!(
         "Loaded {} index symbols, {} total",
         cfg.index_symbols().len(),
         cfg.all_symbols().len()
     );
 
-    let pool = db::init_pool()
+    let pool = db::init_pool(&settings.database)
         .await
         .expect("Failed to initialize database");
-    let config = Arc::new(cfg);
+    // Swapped in place by reloads (SIGHUP or POST /api/v1/admin/jobs/reload).
+    let config = config::SharedConfig::new(cfg);
 
-    // Spawn background fetcher.
-    fetcher::spawn(pool.clone(), config.clone());
+    let stores = store::Stores::sql(pool.clone());
 
-    let state = AppState { pool, config };
+    // Live updates for /api/stream subscribers.
+    let events = events::Events::new();
 
-    let api = Router::new()
-        .route("/api/health", axum::routing::get(routes::health))
-        .route("/api/index", axum::routing::get(routes::get_index))
-        .route(
-            "/api/index/history",
-            axum::routing::get(routes::get_index_history),
-        )
-        .route("/api/stocks", axum::routing::get(routes::get_stocks))
-        .route(
-            "/api/stocks/{symbol}",
-            axum::routing::get(routes::get_stock),
-        )
-        .route("/api/sectors", axum::routing::get(routes::get_sectors))
-        .route(
-            "/api/benchmarks/history",
-            axum::routing::get(routes::get_benchmark_history),
-        )
-        .route("/api/config", axum::routing::get(routes::get_config))
-        .with_state(state);
+    // Latest quotes, profiles and weights served by the API, kept current by
+    // the fetcher. /api/config reports the settings, so they key its ETags too.
+    let cache = cache::Cache::new(&format!("{:?}", settings.redacted()));
+    if let Err(e) = cache.refresh(&stores, &config.load()).await {
+        tracing::error!("Failed to load latest-state cache: {}", e);
+    }
+
+    // Spawn background fetcher; admin jobs share its run lock.
+    let fetcher = fetcher::Fetcher::new(
+        stores.clone(),
+        config.clone(),
+        events.clone(),
+        cache.clone(),
+        settings.clone(),
+    );
+    fetcher.spawn();
+    let jobs = jobs::Jobs::default();
+    #[cfg(unix)]
+    reload_on_sighup(fetcher.clone(), jobs.clone());
+
+    // Spawn intraday retention / downsampling job.
+    compaction::spawn(
+        pool.clone(),
+        stores.clone(),
+        cache.clone(),
+        config.clone(),
+        &settings.compaction,
+    );
+
+    // Spawn scheduled online backups.
+    let backups = backup::BackupConfig::new(&settings.backups);
+    backup::spawn(pool.clone(), backups.clone());
 
-    // In production, serve static files from /app/dist; in dev, Vite proxies.
-    let app = api
-        .fallback_service(ServeDir::new("dist"))
-        .layer(CorsLayer::permissive());
+    let state = AppState {
+        stores,
+        config,
+        pool,
+        backups,
+        events,
+        cache,
+        fetcher,
+        jobs,
+        settings: settings.clone(),
+    };
 
-    let addr = std::env::var("BIND_ADDR").unwrap_or_else(|_| "0.0.0.0:8080".to_string());
+    // In production, serve the built PWA; in dev, Vite proxies.
+    let app = api::router(state)
+        .fallback_service(ServeDir::new(&settings.server.static_dir))
+        .layer(api::cors(&settings.server.cors_origins));
+
+    let addr = &settings.server.bind_addr;
     tracing::info!("Listening on {}", addr);
 
-    let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
+    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
     axum::serve(listener, app).await.unwrap();
 }
+
+/// Reload `stocks.toml` whenever the process receives SIGHUP, as a job
+/// visible at /api/v1/admin/jobs.
+#[cfg(unix)]
+fn reload_on_sighup(fetcher: fetcher::Fetcher, jobs: jobs::Jobs) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(s) => s,
+        Err(e) => {
+            tracing::error!("Failed to listen for SIGHUP, config reload disabled: {}", e);
+            return;
+        }
+    };
+    tokio::spawn(async move {
+        while hangups.recv().await.is_some() {
+            tracing::info!("SIGHUP received, reloading config");
+            let fetcher = fetcher.clone();
+            jobs.spawn("reload", async move { fetcher.reload_config().await });
+        }
+    });
+}
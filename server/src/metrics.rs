@@ -0,0 +1,76 @@
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Counters for background maintenance jobs, exported at `/api/metrics`.
+pub static COMPACTION: CompactionMetrics = CompactionMetrics::new();
+
+pub struct CompactionMetrics {
+    pub runs: AtomicU64,
+    pub failures: AtomicU64,
+    pub rows_removed: AtomicU64,
+    pub bars_written: AtomicU64,
+    pub last_run_unix: AtomicU64,
+    pub last_duration_ms: AtomicU64,
+}
+
+impl CompactionMetrics {
+    const fn new() -> Self {
+        CompactionMetrics {
+            runs: AtomicU64::new(0),
+            failures: AtomicU64::new(0),
+            rows_removed: AtomicU64::new(0),
+            bars_written: AtomicU64::new(0),
+            last_run_unix: AtomicU64::new(0),
+            last_duration_ms: AtomicU64::new(0),
+        }
+    }
+}
+
+/// Render all metrics in the Prometheus text exposition format.
+pub fn render() -> String {
+    let c = &COMPACTION;
+    let mut out = String::new();
+    for (name, kind, help, value) in [
+        (
+            "aiindex_compaction_runs_total",
+            "counter",
+            "Completed compaction runs.",
+            &c.runs,
+        ),
+        (
+            "aiindex_compaction_failures_total",
+            "counter",
+            "Compaction runs that hit a database error.",
+            &c.failures,
+        ),
+        (
+            "aiindex_compaction_rows_removed_total",
+            "counter",
+            "Intraday rows folded into daily bars.",
+            &c.rows_removed,
+        ),
+        (
+            "aiindex_compaction_bars_written_total",
+            "counter",
+            "Daily bars written by compaction.",
+            &c.bars_written,
+        ),
+        (
+            "aiindex_compaction_last_run_timestamp_seconds",
+            "gauge",
+            "Unix time the last compaction run finished.",
+            &c.last_run_unix,
+        ),
+        (
+            "aiindex_compaction_last_duration_milliseconds",
+            "gauge",
+            "Wall time of the last compaction run.",
+            &c.last_duration_ms,
+        ),
+    ] {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        let _ = writeln!(out, "{} {}", name, value.load(Ordering::Relaxed));
+    }
+    out
+}
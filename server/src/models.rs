@@ -1,4 +1,13 @@
 use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+// Types that cross the API live in the client crate, shared with its users.
+pub use aiindex_client::types::{
+    BackfillRequest, BackupSettings, BenchmarkPricePoint, CompactionSettings, ConfigInfo,
+    ErrorBody, ErrorEnvelope, FeatureSettings, FetchRun, FetcherSettings, IndexHistory,
+    IndexSnapshot, Job, OhlcBar, PriceRecord, RecomputeRequest, RuntimeSettings, SectorSummary,
+    StockBar, StockDetail, StockHistory,
+};
 
 /// Finnhub quote response.
 #[derive(Debug, Deserialize)]
@@ -52,48 +61,38 @@ pub struct YahooQuote {
     pub close: Option<Vec<Option<f64>>>,
 }
 
-/// A price record stored in the database.
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[allow(dead_code)]
-pub struct PriceRecord {
+/// A daily OHLCV bar loaded from an external source.
+#[derive(Debug, Clone)]
+pub struct PriceBar {
     pub symbol: String,
-    pub price: f64,
+    /// Session close, RFC 3339.
+    pub timestamp: String,
+    pub open: Option<f64>,
+    pub high: Option<f64>,
+    pub low: Option<f64>,
+    pub close: f64,
+    pub volume: Option<f64>,
     pub change: Option<f64>,
     pub change_pct: Option<f64>,
     pub market_cap: Option<f64>,
-    pub timestamp: String,
 }
 
-/// A computed index snapshot.
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct IndexSnapshot {
-    pub value: f64,
-    pub daily_change: Option<f64>,
-    pub daily_change_pct: Option<f64>,
+/// A stored price or snapshot row with whatever range it carries. Open, high
+/// and low are NULL on raw points and set on compacted or imported bars.
+#[derive(Debug, Clone, FromRow)]
+pub struct OhlcPoint {
     pub timestamp: String,
+    pub open: Option<f64>,
+    pub high: Option<f64>,
+    pub low: Option<f64>,
+    pub close: f64,
+    #[sqlx(default)]
+    pub volume: Option<f64>,
 }
 
-/// Base price for a stock, used in index calculation.
-#[derive(Debug, Clone, Serialize)]
-#[allow(dead_code)]
-pub struct BasePrice {
-    pub symbol: String,
-    pub price: f64,
-    pub recorded_at: String,
-}
-
-/// Stock detail returned by the API.
-#[derive(Debug, Serialize)]
-pub struct StockDetail {
-    pub symbol: String,
-    pub sector: String,
-    pub sector_label: String,
-    pub price: f64,
-    pub change: Option<f64>,
-    pub change_pct: Option<f64>,
-    pub market_cap: Option<f64>,
-    pub weight: Option<f64>,
-    pub timestamp: String,
+/// Company profile metadata cached from Finnhub.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, FromRow)]
+pub struct StockProfile {
     pub name: Option<String>,
     pub exchange: Option<String>,
     pub industry: Option<String>,
@@ -102,28 +101,34 @@ pub struct StockDetail {
     pub country: Option<String>,
 }
 
-/// Sector summary returned by the API.
-#[derive(Debug, Serialize)]
-pub struct SectorSummary {
-    pub key: String,
-    pub label: String,
-    pub symbols: Vec<String>,
-    pub total_weight: f64,
-    pub avg_change_pct: f64,
+/// Base price for a stock, used in index calculation.
+#[derive(Debug, Clone, Serialize)]
+#[allow(dead_code)]
+pub struct BasePrice {
+    pub symbol: String,
+    pub price: f64,
+    pub recorded_at: String,
 }
 
-/// A single price point for benchmark history.
+/// A constituent's blended weight at one reference session close.
 #[derive(Debug, Clone, Serialize)]
-pub struct BenchmarkPricePoint {
-    pub price: f64,
+pub struct WeightPoint {
     pub timestamp: String,
+    pub symbol: String,
+    pub market_cap: Option<f64>,
+    pub weight: f64,
 }
 
-/// Config info returned by /api/config.
-#[derive(Debug, Serialize)]
-pub struct ConfigInfo {
-    pub base_value: f64,
-    pub market_cap_weight_pct: u32,
-    pub index_stock_count: usize,
-    pub benchmark_symbols: Vec<String>,
+/// A stored API key. The key itself is never kept, only its hash.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ApiKey {
+    pub id: i64,
+    pub name: String,
+    /// Leading characters of the key, to tell keys apart.
+    pub prefix: String,
+    /// `read` or `admin`.
+    pub role: String,
+    pub created_at: String,
+    pub last_used_at: Option<String>,
+    pub revoked_at: Option<String>,
 }
@@ -0,0 +1,83 @@
+//! OpenAPI 3 description of the HTTP API, served at `/api/openapi.json`.
+
+use crate::models::*;
+use crate::routes;
+use aiindex_client::types::BackupInfo;
+use axum::response::Json;
+use utoipa::openapi::security::{Http, HttpAuthScheme, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "AI Index API",
+        description = "Hybrid-weighted index of AI stocks: snapshots, quotes, history and admin operations."
+    ),
+    paths(
+        routes::health,
+        routes::get_index,
+        routes::get_index_history,
+        routes::get_stocks,
+        routes::get_stock,
+        routes::get_stock_history,
+        routes::get_sectors,
+        routes::get_benchmark_history,
+        routes::get_config,
+        routes::get_metrics,
+        routes::stream,
+        routes::export_dataset,
+        routes::get_fetch_runs,
+        routes::list_backups,
+        routes::create_backup,
+        routes::run_quotes,
+        routes::run_profiles,
+        routes::run_backfill,
+        routes::run_recompute,
+        routes::run_reload,
+        routes::list_jobs,
+        routes::get_job,
+    ),
+    components(schemas(
+        IndexSnapshot,
+        IndexHistory,
+        OhlcBar,
+        StockBar,
+        StockHistory,
+        PriceRecord,
+        StockDetail,
+        SectorSummary,
+        BenchmarkPricePoint,
+        ConfigInfo,
+        RuntimeSettings,
+        FetcherSettings,
+        CompactionSettings,
+        BackupSettings,
+        FeatureSettings,
+        FetchRun,
+        BackupInfo,
+        Job,
+        BackfillRequest,
+        RecomputeRequest,
+        ErrorEnvelope,
+        ErrorBody,
+    )),
+    modifiers(&BearerAuth)
+)]
+pub struct ApiDoc;
+
+/// Admin routes take `Authorization: Bearer <API key>`.
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+        );
+    }
+}
+
+pub async fn spec() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
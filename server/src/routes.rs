@@ -1,240 +1,348 @@
-use crate::config::StocksConfig;
+use crate::app_config::AppConfig;
+use crate::backup::{self, BackupConfig, BackupInfo};
+use crate::cache::Cache;
+use crate::config::{Sector, SharedConfig};
+use crate::error::{ApiError, OptionalJsonBody, Path, Query};
+use crate::events::{Event, Events, Topic};
+use crate::export::{self, Dataset, Format};
+use crate::fetcher::Fetcher;
+use crate::history::{self, Interval, Range};
+use crate::index;
+use crate::jobs::Jobs;
+use crate::metrics;
 use crate::models::*;
-use axum::extract::{Path, Query, State};
-use axum::http::StatusCode;
-use axum::response::Json;
+use crate::store::Stores;
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{header, StatusCode};
+use axum::response::sse::{self, KeepAlive, Sse};
+use axum::response::{IntoResponse, Json, Response};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::Deserialize;
-use sqlx::SqlitePool;
+use sqlx::AnyPool;
+use std::collections::HashMap;
+use std::convert::Infallible;
 use std::sync::Arc;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::wrappers::{BroadcastStream, ReceiverStream};
+use tokio_stream::{Stream, StreamExt};
+use tokio_util::io::ReaderStream;
+use utoipa::IntoParams;
 
 #[derive(Clone)]
 pub struct AppState {
-    pub pool: SqlitePool,
-    pub config: Arc<StocksConfig>,
+    pub stores: Stores,
+    pub config: SharedConfig,
+    pub pool: AnyPool,
+    pub backups: BackupConfig,
+    pub events: Events,
+    pub cache: Cache,
+    pub fetcher: Fetcher,
+    pub jobs: Jobs,
+    pub settings: Arc<AppConfig>,
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/health",
+    tag = "meta",
+    responses((status = 200, description = "The server is up", body = String, content_type = "text/plain"))
+)]
 pub async fn health() -> &'static str {
     "ok"
 }
 
-pub async fn get_index(State(state): State<AppState>) -> Json<serde_json::Value> {
-    let row = sqlx::query_as::<_, (f64, Option<f64>, Option<f64>, String)>(
-        "SELECT value, daily_change, daily_change_pct, timestamp
-         FROM index_snapshots ORDER BY timestamp DESC LIMIT 1",
+#[utoipa::path(
+    get,
+    path = "/api/v1/index",
+    tag = "index",
+    responses(
+        (status = 200, description = "Latest index snapshot", body = IndexSnapshot),
+        (status = 503, description = "No snapshot computed yet", body = ErrorEnvelope)
     )
-    .fetch_optional(&state.pool)
-    .await
-    .ok()
-    .flatten();
-
-    match row {
-        Some((value, daily_change, daily_change_pct, timestamp)) => {
-            Json(serde_json::json!(IndexSnapshot {
-                value,
-                daily_change,
-                daily_change_pct,
-                timestamp,
-            }))
-        }
-        None => Json(serde_json::json!({
-            "value": null,
-            "message": "No data available yet"
-        })),
+)]
+pub async fn get_index(State(state): State<AppState>) -> Result<Json<IndexSnapshot>, ApiError> {
+    state
+        .cache
+        .load()
+        .snapshot
+        .clone()
+        .map(Json)
+        .ok_or_else(|| ApiError::Unavailable("no index data available yet".to_string()))
+}
+
+/// Most rows a `limit` may ask for; larger values are clamped.
+const MAX_LIMIT: i64 = 10_000;
+
+/// A requested row count, `default` when absent. Zero or negative counts
+/// are rejected rather than read as "no limit".
+fn row_limit(requested: Option<i64>, default: i64) -> Result<i64, ApiError> {
+    match requested {
+        None => Ok(default),
+        Some(n) if n <= 0 => Err(ApiError::Invalid(format!(
+            "'limit' must be positive, got {}",
+            n
+        ))),
+        Some(n) => Ok(n.min(MAX_LIMIT)),
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct HistoryQuery {
+    /// Rows in the legacy form, newest first. Default 100, at most 10000.
     pub limit: Option<i64>,
+    /// Start of the window, inclusive: RFC 3339 or `YYYY-MM-DD`.
+    pub from: Option<String>,
+    /// End of the window, exclusive: RFC 3339 or `YYYY-MM-DD`.
+    pub to: Option<String>,
+    /// One of 15m, 1h, 1d (default), 1w or 1mo.
+    pub interval: Option<String>,
+}
+
+impl HistoryQuery {
+    /// The requested window and bar width, or `None` for the legacy
+    /// newest-first `limit` form.
+    fn window(&self) -> Result<Option<(history::Range, Interval)>, ApiError> {
+        if self.from.is_none() && self.to.is_none() && self.interval.is_none() {
+            return Ok(None);
+        }
+        let range = history::Range::parse(self.from.as_deref(), self.to.as_deref())
+            .map_err(ApiError::Invalid)?;
+        let interval = self
+            .interval
+            .as_deref()
+            .unwrap_or("1d")
+            .parse()
+            .map_err(ApiError::Invalid)?;
+        Ok(Some((range, interval)))
+    }
 }
 
+/// Index history. With `from`, `to` or `interval`, returns OHLC bars oldest
+/// first; otherwise the latest `limit` snapshots, newest first.
+#[utoipa::path(
+    get,
+    path = "/api/v1/index/history",
+    tag = "index",
+    params(HistoryQuery),
+    responses(
+        (status = 200, description = "Bars, or snapshots in the legacy form", body = IndexHistory),
+        (status = 422, description = "Invalid range, interval or limit", body = ErrorEnvelope),
+        (status = 500, description = "Server error", body = ErrorEnvelope)
+    )
+)]
 pub async fn get_index_history(
     State(state): State<AppState>,
     Query(q): Query<HistoryQuery>,
-) -> Json<Vec<IndexSnapshot>> {
-    let limit = q.limit.unwrap_or(100);
-    let rows = sqlx::query_as::<_, (f64, Option<f64>, Option<f64>, String)>(
-        "SELECT value, daily_change, daily_change_pct, timestamp
-         FROM index_snapshots ORDER BY timestamp DESC LIMIT ?",
-    )
-    .bind(limit)
-    .fetch_all(&state.pool)
-    .await
-    .unwrap_or_default();
-
-    let snapshots: Vec<IndexSnapshot> = rows
-        .into_iter()
-        .map(
-            |(value, daily_change, daily_change_pct, timestamp)| IndexSnapshot {
-                value,
-                daily_change,
-                daily_change_pct,
-                timestamp,
-            },
-        )
-        .collect();
-
-    Json(snapshots)
+) -> Result<Json<IndexHistory>, ApiError> {
+    let Some((range, interval)) = q.window()? else {
+        let limit = row_limit(q.limit, 100)?;
+        let snapshots = state.stores.snapshots.history(limit).await?;
+        return Ok(Json(IndexHistory::Snapshots(snapshots)));
+    };
+
+    let (from, to) = range.bounds();
+    let points = state.stores.snapshots.ohlc(&from, &to).await?;
+    let bars = history::resample(&points, interval, state.config.load().reference_exchange());
+    Ok(Json(IndexHistory::Bars(bars)))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/stocks",
+    tag = "stocks",
+    responses((status = 200, description = "Every quoted constituent in sector order, then benchmarks", body = Vec<StockDetail>))
+)]
 pub async fn get_stocks(State(state): State<AppState>) -> Json<Vec<StockDetail>> {
+    let config = state.config.load();
+    let latest = state.cache.load();
     let mut stocks = Vec::new();
 
-    // Compute weights for display.
-    let weights = compute_weights(&state.pool, &state.config).await;
-
-    for (sector_key, sector) in &state.config.sectors {
+    for (sector_key, sector) in &config.sectors {
         for sym in &sector.symbols {
-            let latest = sqlx::query_as::<_, (f64, Option<f64>, Option<f64>, Option<f64>, String)>(
-                "SELECT price, change, change_pct, market_cap, timestamp
-                 FROM prices WHERE symbol = ? ORDER BY timestamp DESC LIMIT 1",
-            )
-            .bind(sym)
-            .fetch_optional(&state.pool)
-            .await
-            .ok()
-            .flatten();
-
-            if let Some((price, change, change_pct, market_cap, timestamp)) = latest {
-                let (name, exchange, industry, weburl, logo, country) =
-                    get_profile(&state.pool, sym).await;
-                stocks.push(StockDetail {
-                    symbol: sym.clone(),
-                    sector: sector_key.clone(),
-                    sector_label: sector.label.clone(),
-                    price,
-                    change,
-                    change_pct,
-                    market_cap,
-                    weight: weights.get(sym).copied(),
-                    timestamp,
-                    name,
-                    exchange,
-                    industry,
-                    weburl,
-                    logo,
-                    country,
-                });
+            if let Some(price) = latest.prices.get(sym) {
+                stocks.push(stock_detail(
+                    price.clone(),
+                    sector_key,
+                    Some(sector),
+                    latest.weights.get(sym).copied(),
+                    latest.profile(sym),
+                ));
             }
         }
     }
 
     // Add benchmarks.
-    for sym in &state.config.benchmarks.symbols {
-        let latest = sqlx::query_as::<_, (f64, Option<f64>, Option<f64>, Option<f64>, String)>(
-            "SELECT price, change, change_pct, market_cap, timestamp
-             FROM prices WHERE symbol = ? ORDER BY timestamp DESC LIMIT 1",
-        )
-        .bind(sym)
-        .fetch_optional(&state.pool)
-        .await
-        .ok()
-        .flatten();
-
-        if let Some((price, change, change_pct, market_cap, timestamp)) = latest {
-            let (name, exchange, industry, weburl, logo, country) =
-                get_profile(&state.pool, sym).await;
-            stocks.push(StockDetail {
-                symbol: sym.clone(),
-                sector: "benchmarks".to_string(),
-                sector_label: "Benchmarks".to_string(),
-                price,
-                change,
-                change_pct,
-                market_cap,
-                weight: None,
-                timestamp,
-                name,
-                exchange,
-                industry,
-                weburl,
-                logo,
-                country,
-            });
+    for sym in &config.benchmarks.symbols {
+        if let Some(price) = latest.prices.get(sym) {
+            stocks.push(stock_detail(
+                price.clone(),
+                "benchmarks",
+                None,
+                None,
+                latest.profile(sym),
+            ));
         }
     }
 
     Json(stocks)
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/stocks/{symbol}",
+    tag = "stocks",
+    params(("symbol" = String, Path, description = "Ticker symbol, case-insensitive")),
+    responses(
+        (status = 200, description = "Latest quote, weight and profile", body = StockDetail),
+        (status = 404, description = "Symbol is not configured", body = ErrorEnvelope),
+        (status = 503, description = "No quote stored yet", body = ErrorEnvelope)
+    )
+)]
 pub async fn get_stock(
     State(state): State<AppState>,
     Path(symbol): Path<String>,
-) -> Result<Json<StockDetail>, StatusCode> {
+) -> Result<Json<StockDetail>, ApiError> {
+    let config = state.config.load();
     let sym = symbol.to_uppercase();
+    if !config.all_symbols().contains(&sym) {
+        return Err(ApiError::NotFound(format!("unknown symbol {}", sym)));
+    }
 
-    // Find sector for this symbol.
-    let (sector_key, sector_label) = state
-        .config
+    // Find sector for this symbol; not in a sector means a benchmark.
+    let (sector_key, sector) = match config
         .sectors
         .iter()
         .find(|(_, s)| s.symbols.contains(&sym))
-        .map(|(k, s)| (k.clone(), s.label.clone()))
-        .unwrap_or_else(|| {
-            if state.config.benchmarks.symbols.contains(&sym) {
-                ("benchmarks".to_string(), "Benchmarks".to_string())
-            } else {
-                ("unknown".to_string(), "Unknown".to_string())
-            }
-        });
+    {
+        Some((key, sector)) => (key.as_str(), Some(sector)),
+        None => ("benchmarks", None),
+    };
+
+    let latest = state.cache.load();
+    let price = latest
+        .prices
+        .get(&sym)
+        .ok_or_else(|| ApiError::Unavailable(format!("no quote for {} yet", sym)))?;
+
+    Ok(Json(stock_detail(
+        price.clone(),
+        sector_key,
+        sector,
+        latest.weights.get(&sym).copied(),
+        latest.profile(&sym),
+    )))
+}
 
-    let latest = sqlx::query_as::<_, (f64, Option<f64>, Option<f64>, Option<f64>, String)>(
-        "SELECT price, change, change_pct, market_cap, timestamp
-         FROM prices WHERE symbol = ? ORDER BY timestamp DESC LIMIT 1",
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct StockHistoryOptions {
+    /// Include the stock's index weight and contribution with each bar.
+    #[serde(default)]
+    pub weights: bool,
+}
+
+/// Price history for one symbol, with the same forms as `/api/v1/index/history`.
+#[utoipa::path(
+    get,
+    path = "/api/v1/stocks/{symbol}/history",
+    tag = "stocks",
+    params(
+        ("symbol" = String, Path, description = "Ticker symbol, case-insensitive"),
+        HistoryQuery,
+        StockHistoryOptions
+    ),
+    responses(
+        (status = 200, description = "Bars, or quotes in the legacy form", body = StockHistory),
+        (status = 404, description = "Symbol is not configured", body = ErrorEnvelope),
+        (status = 422, description = "Invalid range, interval or limit", body = ErrorEnvelope),
+        (status = 500, description = "Server error", body = ErrorEnvelope)
     )
-    .bind(&sym)
-    .fetch_optional(&state.pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-    .ok_or(StatusCode::NOT_FOUND)?;
-
-    let weights = compute_weights(&state.pool, &state.config).await;
-    let (price, change, change_pct, market_cap, timestamp) = latest;
-
-    let (name, exchange, industry, weburl, logo, country) = get_profile(&state.pool, &sym).await;
-
-    Ok(Json(StockDetail {
-        symbol: sym.clone(),
-        sector: sector_key,
-        sector_label,
-        price,
-        change,
-        change_pct,
-        market_cap,
-        weight: weights.get(&sym).copied(),
-        timestamp,
-        name,
-        exchange,
-        industry,
-        weburl,
-        logo,
-        country,
-    }))
+)]
+pub async fn get_stock_history(
+    State(state): State<AppState>,
+    Path(symbol): Path<String>,
+    Query(q): Query<HistoryQuery>,
+    Query(opts): Query<StockHistoryOptions>,
+) -> Result<Json<StockHistory>, ApiError> {
+    let config = state.config.load();
+    let sym = symbol.to_uppercase();
+    if !config.all_symbols().contains(&sym) {
+        return Err(ApiError::NotFound(format!("unknown symbol {}", sym)));
+    }
+
+    let Some((range, interval)) = q.window()? else {
+        let limit = row_limit(q.limit, 100)?;
+        let rows = state.stores.prices.history(&sym, limit).await?;
+        return Ok(Json(StockHistory::Prices(rows)));
+    };
+
+    let (from, to) = range.bounds();
+    let points = state.stores.prices.ohlc(&sym, &from, &to).await?;
+    let bars = history::resample(&points, interval, config.exchange_for(&sym));
+
+    if !opts.weights {
+        return Ok(Json(StockHistory::Bars(bars)));
+    }
+
+    let closes: Vec<DateTime<Utc>> = bars
+        .iter()
+        .map(|b| {
+            DateTime::parse_from_rfc3339(&b.closed_at)
+                .map(|t| t.with_timezone(&Utc))
+                .unwrap_or_default()
+        })
+        .collect();
+    let weights = index::symbol_weights(&state.stores, &config, &sym, &closes).await?;
+    let base = state
+        .stores
+        .prices
+        .base_price(&sym)
+        .await?
+        .filter(|b| *b > 0.0);
+    let base_value = config.settings.base_value;
+
+    let out: Vec<StockBar> = bars
+        .into_iter()
+        .zip(weights)
+        .map(|(bar, weight)| {
+            let contribution = weight
+                .zip(base)
+                .map(|(w, base)| w * bar.close / base * base_value);
+            StockBar {
+                bar,
+                weight,
+                contribution,
+            }
+        })
+        .collect();
+    Ok(Json(StockHistory::WeightedBars(out)))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/sectors",
+    tag = "index",
+    responses((status = 200, description = "Weight and average change per sector, in configured order", body = Vec<SectorSummary>))
+)]
 pub async fn get_sectors(State(state): State<AppState>) -> Json<Vec<SectorSummary>> {
-    let weights = compute_weights(&state.pool, &state.config).await;
+    let config = state.config.load();
+    let latest = state.cache.load();
     let mut sectors = Vec::new();
 
-    for (key, sector) in &state.config.sectors {
-        let total_weight: f64 = sector.symbols.iter().filter_map(|s| weights.get(s)).sum();
-
-        let mut changes = Vec::new();
-        for sym in &sector.symbols {
-            let change_pct = sqlx::query_as::<_, (Option<f64>,)>(
-                "SELECT change_pct FROM prices WHERE symbol = ? ORDER BY timestamp DESC LIMIT 1",
-            )
-            .bind(sym)
-            .fetch_optional(&state.pool)
-            .await
-            .ok()
-            .flatten()
-            .and_then(|(c,)| c);
-
-            if let Some(c) = change_pct {
-                changes.push(c);
-            }
-        }
+    for (key, sector) in &config.sectors {
+        let total_weight: f64 = sector
+            .symbols
+            .iter()
+            .filter_map(|s| latest.weights.get(s))
+            .sum();
+
+        let changes: Vec<f64> = sector
+            .symbols
+            .iter()
+            .filter_map(|s| latest.prices.get(s).and_then(|r| r.change_pct))
+            .collect();
 
         let avg_change_pct = if changes.is_empty() {
             0.0
@@ -245,6 +353,9 @@ pub async fn get_sectors(State(state): State<AppState>) -> Json<Vec<SectorSummar
         sectors.push(SectorSummary {
             key: key.clone(),
             label: sector.label.clone(),
+            color: sector.color.clone(),
+            short_label: sector.short_label.clone(),
+            description: sector.description.clone(),
             symbols: sector.symbols.clone(),
             total_weight,
             avg_change_pct,
@@ -254,104 +365,520 @@ pub async fn get_sectors(State(state): State<AppState>) -> Json<Vec<SectorSummar
     Json(sectors)
 }
 
+/// Latest prices per benchmark. Only `limit` is supported; ranges and bars
+/// come from `/api/v1/stocks/{symbol}/history`, which serves benchmarks too.
+#[utoipa::path(
+    get,
+    path = "/api/v1/benchmarks/history",
+    tag = "stocks",
+    params(("limit" = Option<i64>, Query, description = "Points per benchmark, default 365, at most 10000")),
+    responses(
+        (status = 200, description = "Prices per benchmark symbol, newest first", body = HashMap<String, Vec<BenchmarkPricePoint>>),
+        (status = 422, description = "Invalid limit, or `from`, `to` or `interval` given", body = ErrorEnvelope),
+        (status = 500, description = "Server error", body = ErrorEnvelope)
+    )
+)]
 pub async fn get_benchmark_history(
     State(state): State<AppState>,
     Query(q): Query<HistoryQuery>,
-) -> Json<std::collections::HashMap<String, Vec<BenchmarkPricePoint>>> {
-    let limit = q.limit.unwrap_or(365);
-    let mut result = std::collections::HashMap::new();
-
-    for sym in &state.config.benchmarks.symbols {
-        let rows = sqlx::query_as::<_, (f64, String)>(
-            "SELECT price, timestamp FROM prices WHERE symbol = ? ORDER BY timestamp DESC LIMIT ?",
-        )
-        .bind(sym)
-        .bind(limit)
-        .fetch_all(&state.pool)
-        .await
-        .unwrap_or_default();
+) -> Result<Json<HashMap<String, Vec<BenchmarkPricePoint>>>, ApiError> {
+    if q.from.is_some() || q.to.is_some() || q.interval.is_some() {
+        return Err(ApiError::Invalid(
+            "'from', 'to' and 'interval' are not supported here; \
+             use /api/v1/stocks/{symbol}/history for benchmark bars"
+                .into(),
+        ));
+    }
+    let config = state.config.load();
+    let limit = row_limit(q.limit, 365)?;
+    let mut result = HashMap::new();
+
+    for sym in &config.benchmarks.symbols {
+        let rows = state.stores.prices.history(sym, limit).await?;
 
         let points: Vec<BenchmarkPricePoint> = rows
             .into_iter()
-            .map(|(price, timestamp)| BenchmarkPricePoint { price, timestamp })
+            .map(|r| BenchmarkPricePoint {
+                price: r.price,
+                timestamp: r.timestamp,
+            })
             .collect();
         result.insert(sym.clone(), points);
     }
 
-    Json(result)
+    Ok(Json(result))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/config",
+    tag = "meta",
+    responses((status = 200, description = "Index settings", body = ConfigInfo))
+)]
 pub async fn get_config(State(state): State<AppState>) -> Json<ConfigInfo> {
+    let config = state.config.load();
     Json(ConfigInfo {
-        base_value: state.config.settings.base_value,
-        market_cap_weight_pct: state.config.settings.market_cap_weight_pct,
-        index_stock_count: state.config.index_symbols().len(),
-        benchmark_symbols: state.config.benchmarks.symbols.clone(),
+        base_value: config.settings.base_value,
+        market_cap_weight_pct: config.settings.market_cap_weight_pct,
+        index_stock_count: config.index_symbols().len(),
+        benchmark_symbols: config.benchmarks.symbols.clone(),
+        runtime: Some(state.settings.redacted()),
     })
 }
 
-/// Look up cached profile info for a symbol.
-async fn get_profile(
-    pool: &SqlitePool,
-    symbol: &str,
-) -> (
-    Option<String>,
-    Option<String>,
-    Option<String>,
-    Option<String>,
-    Option<String>,
-    Option<String>,
-) {
-    sqlx::query_as::<_, (Option<String>, Option<String>, Option<String>, Option<String>, Option<String>, Option<String>)>(
-        "SELECT name, exchange, industry, weburl, logo, country FROM stock_profiles WHERE symbol = ?",
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct FetchRunsQuery {
+    /// Only runs of this kind: quotes, profiles or backfill.
+    pub kind: Option<String>,
+    /// Default 50, at most 10000.
+    pub limit: Option<i64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/fetch-runs",
+    tag = "admin",
+    security(("bearer" = [])),
+    params(FetchRunsQuery),
+    responses(
+        (status = 200, description = "Recent runs, newest first", body = Vec<FetchRun>),
+        (status = 401, description = "Missing or invalid API key", body = ErrorEnvelope),
+        (status = 422, description = "Invalid limit", body = ErrorEnvelope),
+        (status = 500, description = "Server error", body = ErrorEnvelope)
     )
-    .bind(symbol)
-    .fetch_optional(pool)
-    .await
-    .ok()
-    .flatten()
-    .unwrap_or_default()
-}
-
-/// Compute blended weights for all index symbols.
-async fn compute_weights(
-    pool: &SqlitePool,
-    config: &StocksConfig,
-) -> std::collections::HashMap<String, f64> {
-    let index_symbols = config.index_symbols();
-    let mcap_pct = config.mcap_pct();
-    let n = index_symbols.len() as f64;
-    let equal_weight = if n > 0.0 { 1.0 / n } else { 0.0 };
-
-    let mut mcaps: Vec<(String, f64)> = Vec::new();
-
-    for sym in &index_symbols {
-        let mcap = sqlx::query_as::<_, (Option<f64>,)>(
-            "SELECT market_cap FROM prices WHERE symbol = ? ORDER BY timestamp DESC LIMIT 1",
-        )
-        .bind(sym)
-        .fetch_optional(pool)
-        .await
-        .ok()
-        .flatten()
-        .and_then(|(m,)| m)
-        .unwrap_or(1.0);
-
-        mcaps.push((sym.clone(), mcap));
-    }
+)]
+pub async fn get_fetch_runs(
+    State(state): State<AppState>,
+    Query(q): Query<FetchRunsQuery>,
+) -> Result<Json<Vec<FetchRun>>, ApiError> {
+    let limit = row_limit(q.limit, 50)?;
+    let runs = state.stores.runs.recent(q.kind.as_deref(), limit).await?;
+    Ok(Json(runs))
+}
 
-    let total_mcap: f64 = mcaps.iter().map(|(_, m)| m).sum();
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct StreamQuery {
+    /// Comma-separated topics; all topics when absent.
+    pub topics: Option<String>,
+    /// Comma-separated symbols to limit quote events to.
+    pub symbols: Option<String>,
+}
 
-    let mut weights = std::collections::HashMap::new();
-    for (sym, mcap) in &mcaps {
-        let mcap_weight = if total_mcap > 0.0 {
-            mcap / total_mcap
-        } else {
-            equal_weight
+/// Server-Sent Events feed of snapshots, quotes and fetcher runs. Needs a
+/// read key.
+///
+/// Each SSE event is named after its topic and carries the same JSON as the
+/// matching REST resource. A subscriber that falls behind gets a `lagged`
+/// event with the number of events it missed.
+#[utoipa::path(
+    get,
+    path = "/api/v1/stream",
+    tag = "stream",
+    security(("bearer" = [])),
+    params(StreamQuery),
+    responses(
+        (status = 200, description = "Event stream; each event's data is the topic's JSON resource", content_type = "text/event-stream"),
+        (status = 401, description = "Missing or invalid API key", body = ErrorEnvelope),
+        (status = 422, description = "Unknown topic", body = ErrorEnvelope)
+    )
+)]
+pub async fn stream(
+    State(state): State<AppState>,
+    Query(q): Query<StreamQuery>,
+) -> Result<Sse<impl Stream<Item = Result<sse::Event, Infallible>>>, ApiError> {
+    let list = |raw: Option<&str>| -> Vec<String> {
+        raw.unwrap_or("")
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_string)
+            .collect()
+    };
+    let topics: Vec<Topic> = match list(q.topics.as_deref()) {
+        names if names.is_empty() => Topic::ALL.to_vec(),
+        names => names
+            .iter()
+            .map(|n| n.parse())
+            .collect::<Result<_, _>>()
+            .map_err(ApiError::Invalid)?,
+    };
+    let symbols: Vec<String> = list(q.symbols.as_deref())
+        .into_iter()
+        .map(|s| s.to_uppercase())
+        .collect();
+
+    let events = BroadcastStream::new(state.events.subscribe()).filter_map(move |item| {
+        let event = match item {
+            Ok(event) => event,
+            Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+                return Some(Ok(sse::Event::default()
+                    .event("lagged")
+                    .data(skipped.to_string())));
+            }
         };
-        let blended = (mcap_pct * mcap_weight) + ((1.0 - mcap_pct) * equal_weight);
-        weights.insert(sym.clone(), blended);
+        if !topics.contains(&event.topic()) {
+            return None;
+        }
+        if let Event::Quote(q) = &event {
+            if !symbols.is_empty() && !symbols.contains(&q.symbol) {
+                return None;
+            }
+        }
+        Some(Ok(sse::Event::default()
+            .event(event.topic().name())
+            .data(event.data().to_string())))
+    });
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportQuery {
+    /// csv (default) or parquet.
+    pub format: Option<String>,
+    /// Start of the window, inclusive: RFC 3339 or `YYYY-MM-DD`.
+    pub from: Option<String>,
+    /// End of the window, exclusive: RFC 3339 or `YYYY-MM-DD`.
+    pub to: Option<String>,
+}
+
+/// Stream one dataset as a CSV or Parquet download. Needs a read key.
+#[utoipa::path(
+    get,
+    path = "/api/v1/export/{dataset}",
+    tag = "export",
+    security(("bearer" = [])),
+    params(
+        ("dataset" = String, Path, description = "One of prices, snapshots, weights or profiles"),
+        ExportQuery
+    ),
+    responses(
+        (status = 200, description = "CSV or Parquet file download"),
+        (status = 401, description = "Missing or invalid API key", body = ErrorEnvelope),
+        (status = 422, description = "Unknown dataset or format, or invalid range", body = ErrorEnvelope)
+    )
+)]
+pub async fn export_dataset(
+    State(state): State<AppState>,
+    Path(dataset): Path<String>,
+    Query(q): Query<ExportQuery>,
+) -> Result<Response, ApiError> {
+    let config = state.config.load();
+    let dataset: Dataset = dataset.parse()?;
+    let format: Format = q.format.as_deref().unwrap_or("csv").parse()?;
+    let range =
+        history::Range::parse(q.from.as_deref(), q.to.as_deref()).map_err(ApiError::Invalid)?;
+
+    // A failure after the headers are sent ends the body with an error, so
+    // the transfer is aborted instead of looking complete.
+    let (mut writer, reader) = tokio::io::duplex(64 * 1024);
+    let (failed, failure) = tokio::sync::mpsc::channel(1);
+    tokio::spawn(async move {
+        let result =
+            export::write(&state.stores, &config, dataset, format, range, &mut writer).await;
+        if let Err(e) = result {
+            tracing::error!("Export of {} failed mid-stream: {}", dataset.name(), e);
+            let _ = failed.send(std::io::Error::other(e.to_string())).await;
+        }
+    });
+    let body = ReaderStream::new(reader).chain(ReceiverStream::new(failure).map(Err));
+
+    let filename = format!("aiindex-{}.{}", dataset.name(), format.extension());
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        Body::from_stream(body),
+    )
+        .into_response())
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/backups",
+    tag = "admin",
+    security(("bearer" = [])),
+    responses(
+        (status = 201, description = "Backup written", body = BackupInfo),
+        (status = 401, description = "Missing or invalid API key", body = ErrorEnvelope),
+        (status = 501, description = "The database backend has no online backups", body = ErrorEnvelope),
+        (status = 500, description = "Server error", body = ErrorEnvelope)
+    )
+)]
+pub async fn create_backup(
+    State(state): State<AppState>,
+) -> Result<(StatusCode, Json<BackupInfo>), ApiError> {
+    let info = backup::create(&state.pool, &state.backups).await?;
+    tracing::info!(
+        "Backup written to {} ({} bytes)",
+        info.file,
+        info.size_bytes
+    );
+    Ok((StatusCode::CREATED, Json(info)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/backups",
+    tag = "admin",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "Backup files, newest first", body = Vec<BackupInfo>),
+        (status = 401, description = "Missing or invalid API key", body = ErrorEnvelope),
+        (status = 500, description = "Server error", body = ErrorEnvelope)
+    )
+)]
+pub async fn list_backups(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<BackupInfo>>, ApiError> {
+    Ok(Json(backup::list(&state.backups).await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/jobs/quotes",
+    tag = "admin",
+    security(("bearer" = [])),
+    responses(
+        (status = 202, description = "Quote cycle queued", body = Job),
+        (status = 401, description = "Missing or invalid API key", body = ErrorEnvelope)
+    )
+)]
+pub async fn run_quotes(State(state): State<AppState>) -> (StatusCode, Json<Job>) {
+    let fetcher = state.fetcher.clone();
+    let job = state.jobs.spawn("quotes", async move {
+        let cycle = fetcher.quotes().await?;
+        Ok(format!("committed quote cycle {}", cycle))
+    });
+    (StatusCode::ACCEPTED, Json(job))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/jobs/profiles",
+    tag = "admin",
+    security(("bearer" = [])),
+    responses(
+        (status = 202, description = "Profile refresh queued", body = Job),
+        (status = 401, description = "Missing or invalid API key", body = ErrorEnvelope)
+    )
+)]
+pub async fn run_profiles(State(state): State<AppState>) -> (StatusCode, Json<Job>) {
+    let fetcher = state.fetcher.clone();
+    let job = state.jobs.spawn("profiles", async move {
+        let (succeeded, failed) = fetcher.profiles().await?;
+        Ok(format!(
+            "{} profiles refreshed, {} failed",
+            succeeded, failed
+        ))
+    });
+    (StatusCode::ACCEPTED, Json(job))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/jobs/backfill",
+    tag = "admin",
+    security(("bearer" = [])),
+    request_body = BackfillRequest,
+    responses(
+        (status = 202, description = "Backfill queued", body = Job),
+        (status = 401, description = "Missing or invalid API key", body = ErrorEnvelope),
+        (status = 422, description = "Unknown symbol or invalid range", body = ErrorEnvelope)
+    )
+)]
+pub async fn run_backfill(
+    State(state): State<AppState>,
+    OptionalJsonBody(req): OptionalJsonBody<BackfillRequest>,
+) -> Result<(StatusCode, Json<Job>), ApiError> {
+    let config = state.config.load();
+    let range = match (&req.from, &req.to) {
+        (None, None) => None,
+        (from, to) => {
+            Some(Range::parse(from.as_deref(), to.as_deref()).map_err(ApiError::Invalid)?)
+        }
+    };
+    let known = config.all_symbols();
+    let symbols = match req.symbols {
+        Some(symbols) if symbols.is_empty() => {
+            return Err(ApiError::Invalid("'symbols' must not be empty".to_string()))
+        }
+        Some(symbols) => {
+            let symbols: Vec<String> = symbols.iter().map(|s| s.to_uppercase()).collect();
+            if let Some(unknown) = symbols.iter().find(|s| !known.contains(s)) {
+                return Err(ApiError::Invalid(format!("unknown symbol '{}'", unknown)));
+            }
+            symbols
+        }
+        None => known,
+    };
+
+    let fetcher = state.fetcher.clone();
+    let job = state.jobs.spawn("backfill", async move {
+        let (succeeded, failed) = fetcher.backfill(&symbols, range).await;
+        let message = format!("{} symbols backfilled, {} failed", succeeded, failed);
+        if succeeded == 0 && failed > 0 {
+            return Err(message);
+        }
+        Ok(message)
+    });
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/jobs/recompute",
+    tag = "admin",
+    security(("bearer" = [])),
+    request_body = RecomputeRequest,
+    responses(
+        (status = 202, description = "Recompute queued", body = Job),
+        (status = 401, description = "Missing or invalid API key", body = ErrorEnvelope),
+        (status = 422, description = "Invalid date", body = ErrorEnvelope)
+    )
+)]
+pub async fn run_recompute(
+    State(state): State<AppState>,
+    OptionalJsonBody(req): OptionalJsonBody<RecomputeRequest>,
+) -> Result<(StatusCode, Json<Job>), ApiError> {
+    let since = req
+        .since
+        .as_deref()
+        .map(|s| NaiveDate::parse_from_str(s, "%Y-%m-%d"))
+        .transpose()
+        .map_err(|_| ApiError::Invalid("'since' must be a YYYY-MM-DD date".to_string()))?;
+
+    let fetcher = state.fetcher.clone();
+    let job = state.jobs.spawn("recompute", async move {
+        let days = fetcher.recompute(since).await.map_err(|e| e.to_string())?;
+        Ok(format!("{} snapshots recomputed", days))
+    });
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+/// Re-read stocks.toml. When constituents change, the job's summary says a
+/// full recompute is required: until then earlier sessions are computed
+/// without the new symbols and the index steps on the reload day.
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/jobs/reload",
+    tag = "admin",
+    security(("bearer" = [])),
+    responses(
+        (status = 202, description = "Config reload queued", body = Job),
+        (status = 401, description = "Missing or invalid API key", body = ErrorEnvelope),
+        (status = 403, description = "The key lacks the admin role", body = ErrorEnvelope)
+    )
+)]
+pub async fn run_reload(State(state): State<AppState>) -> (StatusCode, Json<Job>) {
+    let fetcher = state.fetcher.clone();
+    let job = state
+        .jobs
+        .spawn("reload", async move { fetcher.reload_config().await });
+    (StatusCode::ACCEPTED, Json(job))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/jobs",
+    tag = "admin",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "Recent jobs, newest first", body = Vec<Job>),
+        (status = 401, description = "Missing or invalid API key", body = ErrorEnvelope)
+    )
+)]
+pub async fn list_jobs(State(state): State<AppState>) -> Json<Vec<Job>> {
+    Json(state.jobs.recent())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/jobs/{id}",
+    tag = "admin",
+    security(("bearer" = [])),
+    params(("id" = u64, Path, description = "Job ID")),
+    responses(
+        (status = 200, description = "The job", body = Job),
+        (status = 401, description = "Missing or invalid API key", body = ErrorEnvelope),
+        (status = 404, description = "Unknown or expired job", body = ErrorEnvelope)
+    )
+)]
+pub async fn get_job(
+    State(state): State<AppState>,
+    Path(id): Path<u64>,
+) -> Result<Json<Job>, ApiError> {
+    state
+        .jobs
+        .get(id)
+        .map(Json)
+        .ok_or_else(|| ApiError::NotFound(format!("no job {}", id)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/metrics",
+    tag = "meta",
+    responses((status = 200, description = "Prometheus text exposition", body = String, content_type = "text/plain"))
+)]
+pub async fn get_metrics() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics::render(),
+    )
+}
+
+/// Assemble the API view of a stock from its latest price and profile.
+/// `sector` is `None` for benchmarks, which have no display metadata.
+fn stock_detail(
+    latest: PriceRecord,
+    sector_key: &str,
+    sector: Option<&Sector>,
+    weight: Option<f64>,
+    profile: StockProfile,
+) -> StockDetail {
+    StockDetail {
+        symbol: latest.symbol,
+        sector: sector_key.to_string(),
+        sector_label: sector.map_or("Benchmarks", |s| &s.label).to_string(),
+        sector_color: sector.and_then(|s| s.color.clone()),
+        sector_short_label: sector.and_then(|s| s.short_label.clone()),
+        sector_description: sector.and_then(|s| s.description.clone()),
+        price: latest.price,
+        change: latest.change,
+        change_pct: latest.change_pct,
+        market_cap: latest.market_cap,
+        weight,
+        timestamp: latest.timestamp,
+        name: profile.name,
+        exchange: profile.exchange,
+        industry: profile.industry,
+        weburl: profile.weburl,
+        logo: profile.logo,
+        country: profile.country,
     }
+}
 
-    weights
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_must_be_positive_and_are_clamped() {
+        assert_eq!(row_limit(None, 100).unwrap(), 100);
+        assert_eq!(row_limit(Some(5), 100).unwrap(), 5);
+        assert_eq!(row_limit(Some(i64::MAX), 100).unwrap(), MAX_LIMIT);
+        assert!(matches!(row_limit(Some(0), 100), Err(ApiError::Invalid(_))));
+        assert!(matches!(
+            row_limit(Some(-1), 100),
+            Err(ApiError::Invalid(_))
+        ));
+    }
 }
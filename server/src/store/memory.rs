@@ -0,0 +1,411 @@
+use super::{
+    run_status, ApiKeyStore, FetchRunStore, PriceStore, ProfileStore, SnapshotStore, StoreResult,
+};
+use crate::models::{
+    ApiKey, FetchRun, IndexSnapshot, OhlcPoint, PriceBar, PriceRecord, StockProfile,
+};
+use async_trait::async_trait;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::RwLock;
+
+/// Stores held in process memory, keyed like the SQL unique indexes.
+#[derive(Default)]
+pub struct MemoryStore {
+    prices: RwLock<HashMap<String, BTreeMap<String, PriceRecord>>>,
+    base_prices: RwLock<HashMap<String, f64>>,
+    snapshots: RwLock<BTreeMap<String, IndexSnapshot>>,
+    profiles: RwLock<HashMap<String, StockProfile>>,
+    runs: RwLock<Vec<FetchRun>>,
+    /// Keys with their hashes.
+    keys: RwLock<Vec<(String, ApiKey)>>,
+    data_version: AtomicI64,
+}
+
+#[async_trait]
+impl PriceStore for MemoryStore {
+    async fn latest(&self, symbol: &str) -> StoreResult<Option<PriceRecord>> {
+        let prices = self.prices.read().unwrap();
+        Ok(prices
+            .get(symbol)
+            .and_then(|rows| rows.values().next_back().cloned()))
+    }
+
+    async fn latest_all(&self) -> StoreResult<Vec<PriceRecord>> {
+        let prices = self.prices.read().unwrap();
+        Ok(prices
+            .values()
+            .filter_map(|rows| rows.values().next_back().cloned())
+            .collect())
+    }
+
+    async fn latest_market_cap(&self, symbol: &str) -> StoreResult<Option<f64>> {
+        let prices = self.prices.read().unwrap();
+        Ok(prices
+            .get(symbol)
+            .and_then(|rows| rows.values().rev().find_map(|r| r.market_cap)))
+    }
+
+    async fn history(&self, symbol: &str, limit: i64) -> StoreResult<Vec<PriceRecord>> {
+        let prices = self.prices.read().unwrap();
+        Ok(prices
+            .get(symbol)
+            .map(|rows| {
+                rows.values()
+                    .rev()
+                    .take(limit.max(0) as usize)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    async fn series(&self, symbol: &str) -> StoreResult<Vec<PriceRecord>> {
+        let prices = self.prices.read().unwrap();
+        Ok(prices
+            .get(symbol)
+            .map(|rows| rows.values().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    async fn range(&self, symbol: &str, from: &str, to: &str) -> StoreResult<Vec<PriceRecord>> {
+        if from >= to {
+            return Ok(Vec::new());
+        }
+        let prices = self.prices.read().unwrap();
+        Ok(prices
+            .get(symbol)
+            .map(|rows| {
+                rows.range(from.to_string()..to.to_string())
+                    .map(|(_, r)| r.clone())
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    async fn before(&self, symbol: &str, timestamp: &str) -> StoreResult<Option<PriceRecord>> {
+        let prices = self.prices.read().unwrap();
+        Ok(prices.get(symbol).and_then(|rows| {
+            rows.range(..timestamp.to_string())
+                .next_back()
+                .map(|(_, r)| r.clone())
+        }))
+    }
+
+    async fn after(&self, symbol: &str, timestamp: &str) -> StoreResult<Option<PriceRecord>> {
+        let prices = self.prices.read().unwrap();
+        Ok(prices.get(symbol).and_then(|rows| {
+            rows.range(timestamp.to_string()..)
+                .next()
+                .map(|(_, r)| r.clone())
+        }))
+    }
+
+    async fn ohlc(&self, symbol: &str, from: &str, to: &str) -> StoreResult<Vec<OhlcPoint>> {
+        Ok(PriceStore::range(self, symbol, from, to)
+            .await?
+            .into_iter()
+            .map(|r| OhlcPoint {
+                timestamp: r.timestamp,
+                open: None,
+                high: None,
+                low: None,
+                close: r.price,
+                volume: None,
+            })
+            .collect())
+    }
+
+    async fn upsert(&self, record: &PriceRecord) -> StoreResult<()> {
+        let mut prices = self.prices.write().unwrap();
+        prices
+            .entry(record.symbol.clone())
+            .or_default()
+            .insert(record.timestamp.clone(), record.clone());
+        Ok(())
+    }
+
+    async fn set_latest_market_cap(&self, symbol: &str, market_cap: f64) -> StoreResult<()> {
+        let mut prices = self.prices.write().unwrap();
+        if let Some(row) = prices
+            .get_mut(symbol)
+            .and_then(|rows| rows.values_mut().next_back())
+        {
+            row.market_cap = Some(market_cap);
+        }
+        Ok(())
+    }
+
+    async fn base_price(&self, symbol: &str) -> StoreResult<Option<f64>> {
+        Ok(self.base_prices.read().unwrap().get(symbol).copied())
+    }
+
+    async fn insert_base_price(
+        &self,
+        symbol: &str,
+        price: f64,
+        _recorded_at: &str,
+    ) -> StoreResult<()> {
+        self.base_prices
+            .write()
+            .unwrap()
+            .entry(symbol.to_string())
+            .or_insert(price);
+        Ok(())
+    }
+
+    async fn record_cycle(&self, records: &[PriceRecord]) -> StoreResult<()> {
+        // Hold both locks so readers never observe a partial cycle.
+        let mut prices = self.prices.write().unwrap();
+        let mut base_prices = self.base_prices.write().unwrap();
+        for r in records {
+            let rows = prices.entry(r.symbol.clone()).or_default();
+            let mut record = r.clone();
+            if record.market_cap.is_none() {
+                record.market_cap = rows.values().rev().find_map(|p| p.market_cap);
+            }
+            rows.insert(record.timestamp.clone(), record);
+            base_prices.entry(r.symbol.clone()).or_insert(r.price);
+        }
+        Ok(())
+    }
+
+    async fn upsert_bars(&self, bars: &[PriceBar]) -> StoreResult<()> {
+        // Only the close is kept; `PriceRecord` has no OHLCV fields.
+        let mut prices = self.prices.write().unwrap();
+        for b in bars {
+            let rows = prices.entry(b.symbol.clone()).or_default();
+            let market_cap = rows
+                .get(&b.timestamp)
+                .and_then(|r| r.market_cap)
+                .or(b.market_cap);
+            rows.insert(
+                b.timestamp.clone(),
+                PriceRecord {
+                    symbol: b.symbol.clone(),
+                    price: b.close,
+                    change: b.change,
+                    change_pct: b.change_pct,
+                    market_cap,
+                    timestamp: b.timestamp.clone(),
+                },
+            );
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SnapshotStore for MemoryStore {
+    async fn latest(&self) -> StoreResult<Option<IndexSnapshot>> {
+        Ok(self.snapshots.read().unwrap().values().next_back().cloned())
+    }
+
+    async fn history(&self, limit: i64) -> StoreResult<Vec<IndexSnapshot>> {
+        let snapshots = self.snapshots.read().unwrap();
+        Ok(snapshots
+            .values()
+            .rev()
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect())
+    }
+
+    async fn range(&self, from: &str, to: &str) -> StoreResult<Vec<IndexSnapshot>> {
+        if from >= to {
+            return Ok(Vec::new());
+        }
+        let snapshots = self.snapshots.read().unwrap();
+        Ok(snapshots
+            .range(from.to_string()..to.to_string())
+            .map(|(_, s)| s.clone())
+            .collect())
+    }
+
+    async fn ohlc(&self, from: &str, to: &str) -> StoreResult<Vec<OhlcPoint>> {
+        Ok(SnapshotStore::range(self, from, to)
+            .await?
+            .into_iter()
+            .map(|s| OhlcPoint {
+                timestamp: s.timestamp,
+                open: None,
+                high: None,
+                low: None,
+                close: s.value,
+                volume: None,
+            })
+            .collect())
+    }
+
+    async fn span(&self) -> StoreResult<Option<(String, String)>> {
+        let snapshots = self.snapshots.read().unwrap();
+        Ok(snapshots
+            .keys()
+            .next()
+            .cloned()
+            .zip(snapshots.keys().next_back().cloned()))
+    }
+
+    async fn upsert(&self, snapshot: &IndexSnapshot) -> StoreResult<()> {
+        self.snapshots
+            .write()
+            .unwrap()
+            .insert(snapshot.timestamp.clone(), snapshot.clone());
+        Ok(())
+    }
+
+    async fn data_version(&self) -> StoreResult<i64> {
+        Ok(self.data_version.load(Ordering::Relaxed))
+    }
+
+    async fn bump_data_version(&self) -> StoreResult<()> {
+        self.data_version.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ProfileStore for MemoryStore {
+    async fn get(&self, symbol: &str) -> StoreResult<Option<StockProfile>> {
+        Ok(self.profiles.read().unwrap().get(symbol).cloned())
+    }
+
+    async fn all(&self) -> StoreResult<Vec<(String, StockProfile)>> {
+        let profiles = self.profiles.read().unwrap();
+        Ok(profiles
+            .iter()
+            .map(|(symbol, p)| (symbol.clone(), p.clone()))
+            .collect())
+    }
+
+    async fn upsert(
+        &self,
+        symbol: &str,
+        profile: &StockProfile,
+        _updated_at: &str,
+    ) -> StoreResult<()> {
+        self.profiles
+            .write()
+            .unwrap()
+            .insert(symbol.to_string(), profile.clone());
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl FetchRunStore for MemoryStore {
+    async fn start(&self, kind: &str, symbols: &[String], started_at: &str) -> StoreResult<i64> {
+        let mut runs = self.runs.write().unwrap();
+        let id = runs.len() as i64 + 1;
+        runs.push(FetchRun {
+            id,
+            kind: kind.to_string(),
+            status: run_status(false, 0, 0),
+            started_at: started_at.to_string(),
+            finished_at: None,
+            symbols: symbols.to_vec(),
+            succeeded: 0,
+            failed: 0,
+            errors: Vec::new(),
+        });
+        Ok(id)
+    }
+
+    async fn finish(
+        &self,
+        id: i64,
+        succeeded: i64,
+        failed: i64,
+        errors: &[String],
+        finished_at: &str,
+    ) -> StoreResult<()> {
+        let mut runs = self.runs.write().unwrap();
+        if let Some(run) = runs.iter_mut().find(|r| r.id == id) {
+            run.status = run_status(true, succeeded, failed);
+            run.finished_at = Some(finished_at.to_string());
+            run.succeeded = succeeded;
+            run.failed = failed;
+            run.errors = errors.to_vec();
+        }
+        Ok(())
+    }
+
+    async fn recent(&self, kind: Option<&str>, limit: i64) -> StoreResult<Vec<FetchRun>> {
+        let runs = self.runs.read().unwrap();
+        Ok(runs
+            .iter()
+            .rev()
+            .filter(|r| kind.is_none_or(|k| r.kind == k))
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect())
+    }
+}
+
+#[async_trait]
+impl ApiKeyStore for MemoryStore {
+    async fn create(
+        &self,
+        name: &str,
+        prefix: &str,
+        key_hash: &str,
+        role: &str,
+        created_at: &str,
+    ) -> StoreResult<i64> {
+        let mut keys = self.keys.write().unwrap();
+        let id = keys.len() as i64 + 1;
+        keys.push((
+            key_hash.to_string(),
+            ApiKey {
+                id,
+                name: name.to_string(),
+                prefix: prefix.to_string(),
+                role: role.to_string(),
+                created_at: created_at.to_string(),
+                last_used_at: None,
+                revoked_at: None,
+            },
+        ));
+        Ok(id)
+    }
+
+    async fn find_active(&self, key_hash: &str) -> StoreResult<Option<ApiKey>> {
+        let keys = self.keys.read().unwrap();
+        Ok(keys
+            .iter()
+            .find(|(hash, k)| hash == key_hash && k.revoked_at.is_none())
+            .map(|(_, k)| k.clone()))
+    }
+
+    async fn touch(&self, id: i64, used_at: &str) -> StoreResult<()> {
+        let mut keys = self.keys.write().unwrap();
+        if let Some((_, key)) = keys.iter_mut().find(|(_, k)| k.id == id) {
+            key.last_used_at = Some(used_at.to_string());
+        }
+        Ok(())
+    }
+
+    async fn revoke(&self, id: i64, revoked_at: &str) -> StoreResult<bool> {
+        let mut keys = self.keys.write().unwrap();
+        match keys
+            .iter_mut()
+            .find(|(_, k)| k.id == id && k.revoked_at.is_none())
+        {
+            Some((_, key)) => {
+                key.revoked_at = Some(revoked_at.to_string());
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn all(&self) -> StoreResult<Vec<ApiKey>> {
+        Ok(self
+            .keys
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(_, k)| k.clone())
+            .collect())
+    }
+}
@@ -0,0 +1,215 @@
+//! Typed storage traits. Routes, the index engine and the fetcher depend on
+//! these rather than on SQL, so they can run against an in-memory store.
+
+mod memory;
+mod sql;
+#[cfg(test)]
+mod tests;
+
+use crate::models::{
+    ApiKey, FetchRun, IndexSnapshot, OhlcPoint, PriceBar, PriceRecord, StockProfile,
+};
+use async_trait::async_trait;
+use sqlx::AnyPool;
+use std::sync::Arc;
+
+pub use memory::MemoryStore;
+pub use sql::SqlStore;
+
+pub type StoreResult<T> = Result<T, sqlx::Error>;
+
+/// Quotes and base prices per symbol.
+#[async_trait]
+pub trait PriceStore: Send + Sync {
+    /// The most recent price row for a symbol.
+    async fn latest(&self, symbol: &str) -> StoreResult<Option<PriceRecord>>;
+
+    /// The most recent price row for every symbol, in one query.
+    async fn latest_all(&self) -> StoreResult<Vec<PriceRecord>>;
+
+    /// The most recent non-null market cap for a symbol.
+    async fn latest_market_cap(&self, symbol: &str) -> StoreResult<Option<f64>>;
+
+    /// Up to `limit` rows for a symbol, newest first.
+    async fn history(&self, symbol: &str, limit: i64) -> StoreResult<Vec<PriceRecord>>;
+
+    /// Every row for a symbol, oldest first.
+    async fn series(&self, symbol: &str) -> StoreResult<Vec<PriceRecord>>;
+
+    /// Rows for a symbol with `from <= timestamp < to`, oldest first.
+    async fn range(&self, symbol: &str, from: &str, to: &str) -> StoreResult<Vec<PriceRecord>>;
+
+    /// The last row for a symbol strictly before `timestamp`.
+    async fn before(&self, symbol: &str, timestamp: &str) -> StoreResult<Option<PriceRecord>>;
+
+    /// The first row for a symbol at or after `timestamp`.
+    async fn after(&self, symbol: &str, timestamp: &str) -> StoreResult<Option<PriceRecord>>;
+
+    /// Like `range`, with each row's open, high, low and volume.
+    async fn ohlc(&self, symbol: &str, from: &str, to: &str) -> StoreResult<Vec<OhlcPoint>>;
+
+    /// Insert a price, replacing any row at the same (symbol, timestamp).
+    async fn upsert(&self, record: &PriceRecord) -> StoreResult<()>;
+
+    /// Set the market cap on the most recent row for a symbol.
+    async fn set_latest_market_cap(&self, symbol: &str, market_cap: f64) -> StoreResult<()>;
+
+    /// The index base price for a symbol.
+    async fn base_price(&self, symbol: &str) -> StoreResult<Option<f64>>;
+
+    /// Record a base price unless one is already set.
+    async fn insert_base_price(
+        &self,
+        symbol: &str,
+        price: f64,
+        recorded_at: &str,
+    ) -> StoreResult<()>;
+
+    /// Store one fetch cycle's quotes in a single transaction. Rows without a
+    /// market cap carry forward the symbol's last known one, and symbols
+    /// without a base price get this quote as their base.
+    async fn record_cycle(&self, records: &[PriceRecord]) -> StoreResult<()>;
+
+    /// Store imported bars in a single transaction, replacing rows at the
+    /// same (symbol, timestamp) but keeping any market cap already recorded.
+    async fn upsert_bars(&self, bars: &[PriceBar]) -> StoreResult<()>;
+}
+
+/// Computed index values.
+#[async_trait]
+pub trait SnapshotStore: Send + Sync {
+    /// The most recent snapshot.
+    async fn latest(&self) -> StoreResult<Option<IndexSnapshot>>;
+
+    /// Up to `limit` snapshots, newest first.
+    async fn history(&self, limit: i64) -> StoreResult<Vec<IndexSnapshot>>;
+
+    /// Snapshots with `from <= timestamp < to`, oldest first.
+    async fn range(&self, from: &str, to: &str) -> StoreResult<Vec<IndexSnapshot>>;
+
+    /// Like `range`, with each row's open, high and low.
+    async fn ohlc(&self, from: &str, to: &str) -> StoreResult<Vec<OhlcPoint>>;
+
+    /// Timestamps of the oldest and newest snapshots.
+    async fn span(&self) -> StoreResult<Option<(String, String)>>;
+
+    /// Insert a snapshot, replacing any row at the same timestamp.
+    async fn upsert(&self, snapshot: &IndexSnapshot) -> StoreResult<()>;
+
+    /// A counter that changes whenever stored history is rewritten.
+    async fn data_version(&self) -> StoreResult<i64>;
+
+    /// Record that stored history was rewritten, e.g. by a recompute,
+    /// backfill, import or compaction.
+    async fn bump_data_version(&self) -> StoreResult<()>;
+}
+
+/// Company profile metadata.
+#[async_trait]
+pub trait ProfileStore: Send + Sync {
+    async fn get(&self, symbol: &str) -> StoreResult<Option<StockProfile>>;
+
+    /// Every cached profile, keyed by symbol.
+    async fn all(&self) -> StoreResult<Vec<(String, StockProfile)>>;
+
+    async fn upsert(
+        &self,
+        symbol: &str,
+        profile: &StockProfile,
+        updated_at: &str,
+    ) -> StoreResult<()>;
+}
+
+/// Audit log of fetcher runs.
+#[async_trait]
+pub trait FetchRunStore: Send + Sync {
+    /// Record the start of a run and return its ID.
+    async fn start(&self, kind: &str, symbols: &[String], started_at: &str) -> StoreResult<i64>;
+
+    /// Record a run's outcome.
+    async fn finish(
+        &self,
+        id: i64,
+        succeeded: i64,
+        failed: i64,
+        errors: &[String],
+        finished_at: &str,
+    ) -> StoreResult<()>;
+
+    /// Up to `limit` runs, newest first, optionally of one kind.
+    async fn recent(&self, kind: Option<&str>, limit: i64) -> StoreResult<Vec<FetchRun>>;
+}
+
+/// API keys, looked up by the hash of the presented key.
+#[async_trait]
+pub trait ApiKeyStore: Send + Sync {
+    /// Store a new key and return its ID.
+    async fn create(
+        &self,
+        name: &str,
+        prefix: &str,
+        key_hash: &str,
+        role: &str,
+        created_at: &str,
+    ) -> StoreResult<i64>;
+
+    /// The unrevoked key with this hash.
+    async fn find_active(&self, key_hash: &str) -> StoreResult<Option<ApiKey>>;
+
+    /// Record that a key was used.
+    async fn touch(&self, id: i64, used_at: &str) -> StoreResult<()>;
+
+    /// Revoke a key. Returns false if no unrevoked key has this ID.
+    async fn revoke(&self, id: i64, revoked_at: &str) -> StoreResult<bool>;
+
+    /// Every key, revoked or not, oldest first.
+    async fn all(&self) -> StoreResult<Vec<ApiKey>>;
+}
+
+/// Summarize a run's outcome for the API.
+pub(crate) fn run_status(finished: bool, succeeded: i64, failed: i64) -> String {
+    match (finished, succeeded, failed) {
+        (false, _, _) => "running",
+        (true, _, 0) => "ok",
+        (true, 0, _) => "failed",
+        _ => "partial",
+    }
+    .to_string()
+}
+
+/// The set of stores shared by the server, fetcher and index engine.
+#[derive(Clone)]
+pub struct Stores {
+    pub prices: Arc<dyn PriceStore>,
+    pub snapshots: Arc<dyn SnapshotStore>,
+    pub profiles: Arc<dyn ProfileStore>,
+    pub runs: Arc<dyn FetchRunStore>,
+    pub keys: Arc<dyn ApiKeyStore>,
+}
+
+impl Stores {
+    /// Stores backed by the configured SQL database.
+    pub fn sql(pool: AnyPool) -> Self {
+        let store = Arc::new(SqlStore::new(pool));
+        Stores {
+            prices: store.clone(),
+            snapshots: store.clone(),
+            profiles: store.clone(),
+            runs: store.clone(),
+            keys: store,
+        }
+    }
+
+    /// Stores held entirely in memory, for tests and tooling.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn memory() -> Self {
+        let store = Arc::new(MemoryStore::default());
+        Stores {
+            prices: store.clone(),
+            snapshots: store.clone(),
+            profiles: store.clone(),
+            runs: store.clone(),
+            keys: store,
+        }
+    }
+}
@@ -0,0 +1,548 @@
+use super::{
+    run_status, ApiKeyStore, FetchRunStore, PriceStore, ProfileStore, SnapshotStore, StoreResult,
+};
+use crate::models::{
+    ApiKey, FetchRun, IndexSnapshot, OhlcPoint, PriceBar, PriceRecord, StockProfile,
+};
+use async_trait::async_trait;
+use sqlx::{AnyPool, FromRow};
+
+const LATEST_MARKET_CAP: &str = "SELECT market_cap FROM prices WHERE symbol = $1 AND market_cap IS NOT NULL ORDER BY timestamp DESC LIMIT 1";
+
+const UPSERT_PRICE: &str =
+    "INSERT INTO prices (symbol, price, change, change_pct, market_cap, timestamp)
+     VALUES ($1, $2, $3, $4, $5, $6)
+     ON CONFLICT(symbol, timestamp) DO UPDATE SET
+       price = excluded.price,
+       change = excluded.change,
+       change_pct = excluded.change_pct,
+       market_cap = excluded.market_cap";
+
+const INSERT_BASE_PRICE: &str = "INSERT INTO base_prices (symbol, price, recorded_at)
+     VALUES ($1, $2, $3)
+     ON CONFLICT(symbol) DO NOTHING";
+
+/// Stores backed by SQLite or Postgres through `sqlx::Any`.
+///
+/// Writes that bind nullable floats use `.persistent(false)`: the Any driver
+/// sends a NULL f64 as float4 on Postgres, and a statement cached from such a
+/// row then rejects the next non-NULL value.
+pub struct SqlStore {
+    pool: AnyPool,
+}
+
+impl SqlStore {
+    pub fn new(pool: AnyPool) -> Self {
+        SqlStore { pool }
+    }
+}
+
+#[async_trait]
+impl PriceStore for SqlStore {
+    async fn latest(&self, symbol: &str) -> StoreResult<Option<PriceRecord>> {
+        sqlx::query_as::<_, PriceRecord>(
+            "SELECT symbol, price, change, change_pct, market_cap, timestamp
+             FROM prices WHERE symbol = $1 ORDER BY timestamp DESC LIMIT 1",
+        )
+        .bind(symbol)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn latest_all(&self) -> StoreResult<Vec<PriceRecord>> {
+        sqlx::query_as::<_, PriceRecord>(
+            "SELECT p.symbol, p.price, p.change, p.change_pct, p.market_cap, p.timestamp
+             FROM prices p
+             JOIN (SELECT symbol, MAX(timestamp) AS timestamp FROM prices GROUP BY symbol) m
+               ON p.symbol = m.symbol AND p.timestamp = m.timestamp",
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn latest_market_cap(&self, symbol: &str) -> StoreResult<Option<f64>> {
+        let row = sqlx::query_as::<_, (Option<f64>,)>(LATEST_MARKET_CAP)
+            .bind(symbol)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.and_then(|(m,)| m))
+    }
+
+    async fn history(&self, symbol: &str, limit: i64) -> StoreResult<Vec<PriceRecord>> {
+        sqlx::query_as::<_, PriceRecord>(
+            "SELECT symbol, price, change, change_pct, market_cap, timestamp
+             FROM prices WHERE symbol = $1 ORDER BY timestamp DESC LIMIT $2",
+        )
+        .bind(symbol)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn series(&self, symbol: &str) -> StoreResult<Vec<PriceRecord>> {
+        sqlx::query_as::<_, PriceRecord>(
+            "SELECT symbol, price, change, change_pct, market_cap, timestamp
+             FROM prices WHERE symbol = $1 ORDER BY timestamp",
+        )
+        .bind(symbol)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn range(&self, symbol: &str, from: &str, to: &str) -> StoreResult<Vec<PriceRecord>> {
+        sqlx::query_as::<_, PriceRecord>(
+            "SELECT symbol, price, change, change_pct, market_cap, timestamp
+             FROM prices WHERE symbol = $1 AND timestamp >= $2 AND timestamp < $3
+             ORDER BY timestamp",
+        )
+        .bind(symbol)
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn before(&self, symbol: &str, timestamp: &str) -> StoreResult<Option<PriceRecord>> {
+        sqlx::query_as::<_, PriceRecord>(
+            "SELECT symbol, price, change, change_pct, market_cap, timestamp
+             FROM prices WHERE symbol = $1 AND timestamp < $2
+             ORDER BY timestamp DESC LIMIT 1",
+        )
+        .bind(symbol)
+        .bind(timestamp)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn after(&self, symbol: &str, timestamp: &str) -> StoreResult<Option<PriceRecord>> {
+        sqlx::query_as::<_, PriceRecord>(
+            "SELECT symbol, price, change, change_pct, market_cap, timestamp
+             FROM prices WHERE symbol = $1 AND timestamp >= $2
+             ORDER BY timestamp LIMIT 1",
+        )
+        .bind(symbol)
+        .bind(timestamp)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn ohlc(&self, symbol: &str, from: &str, to: &str) -> StoreResult<Vec<OhlcPoint>> {
+        sqlx::query_as::<_, OhlcPoint>(
+            "SELECT timestamp, open, high, low, price AS close, volume
+             FROM prices WHERE symbol = $1 AND timestamp >= $2 AND timestamp < $3
+             ORDER BY timestamp",
+        )
+        .bind(symbol)
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn upsert(&self, r: &PriceRecord) -> StoreResult<()> {
+        sqlx::query(UPSERT_PRICE)
+            .persistent(false)
+            .bind(&r.symbol)
+            .bind(r.price)
+            .bind(r.change)
+            .bind(r.change_pct)
+            .bind(r.market_cap)
+            .bind(&r.timestamp)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn set_latest_market_cap(&self, symbol: &str, market_cap: f64) -> StoreResult<()> {
+        sqlx::query(
+            "UPDATE prices SET market_cap = $1
+             WHERE id = (SELECT id FROM prices WHERE symbol = $2 ORDER BY timestamp DESC LIMIT 1)",
+        )
+        .bind(market_cap)
+        .bind(symbol)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn base_price(&self, symbol: &str) -> StoreResult<Option<f64>> {
+        let row = sqlx::query_as::<_, (f64,)>("SELECT price FROM base_prices WHERE symbol = $1")
+            .bind(symbol)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map(|(p,)| p))
+    }
+
+    async fn insert_base_price(
+        &self,
+        symbol: &str,
+        price: f64,
+        recorded_at: &str,
+    ) -> StoreResult<()> {
+        sqlx::query(INSERT_BASE_PRICE)
+            .bind(symbol)
+            .bind(price)
+            .bind(recorded_at)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn record_cycle(&self, records: &[PriceRecord]) -> StoreResult<()> {
+        let mut tx = self.pool.begin().await?;
+        for r in records {
+            let market_cap = match r.market_cap {
+                Some(m) => Some(m),
+                None => sqlx::query_as::<_, (Option<f64>,)>(LATEST_MARKET_CAP)
+                    .bind(&r.symbol)
+                    .fetch_optional(&mut *tx)
+                    .await?
+                    .and_then(|(m,)| m),
+            };
+            sqlx::query(UPSERT_PRICE)
+                .persistent(false)
+                .bind(&r.symbol)
+                .bind(r.price)
+                .bind(r.change)
+                .bind(r.change_pct)
+                .bind(market_cap)
+                .bind(&r.timestamp)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query(INSERT_BASE_PRICE)
+                .bind(&r.symbol)
+                .bind(r.price)
+                .bind(&r.timestamp)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await
+    }
+
+    async fn upsert_bars(&self, bars: &[PriceBar]) -> StoreResult<()> {
+        let mut tx = self.pool.begin().await?;
+        for b in bars {
+            sqlx::query(
+                "INSERT INTO prices (symbol, price, open, high, low, volume, change, change_pct, market_cap, timestamp)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                 ON CONFLICT(symbol, timestamp) DO UPDATE SET
+                   price = excluded.price,
+                   open = excluded.open,
+                   high = excluded.high,
+                   low = excluded.low,
+                   volume = excluded.volume,
+                   change = excluded.change,
+                   change_pct = excluded.change_pct,
+                   market_cap = COALESCE(prices.market_cap, excluded.market_cap)",
+            )
+            .persistent(false)
+            .bind(&b.symbol)
+            .bind(b.close)
+            .bind(b.open)
+            .bind(b.high)
+            .bind(b.low)
+            .bind(b.volume)
+            .bind(b.change)
+            .bind(b.change_pct)
+            .bind(b.market_cap)
+            .bind(&b.timestamp)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await
+    }
+}
+
+#[async_trait]
+impl SnapshotStore for SqlStore {
+    async fn latest(&self) -> StoreResult<Option<IndexSnapshot>> {
+        sqlx::query_as::<_, IndexSnapshot>(
+            "SELECT value, daily_change, daily_change_pct, timestamp
+             FROM index_snapshots ORDER BY timestamp DESC LIMIT 1",
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn history(&self, limit: i64) -> StoreResult<Vec<IndexSnapshot>> {
+        sqlx::query_as::<_, IndexSnapshot>(
+            "SELECT value, daily_change, daily_change_pct, timestamp
+             FROM index_snapshots ORDER BY timestamp DESC LIMIT $1",
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn range(&self, from: &str, to: &str) -> StoreResult<Vec<IndexSnapshot>> {
+        sqlx::query_as::<_, IndexSnapshot>(
+            "SELECT value, daily_change, daily_change_pct, timestamp
+             FROM index_snapshots WHERE timestamp >= $1 AND timestamp < $2
+             ORDER BY timestamp",
+        )
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn ohlc(&self, from: &str, to: &str) -> StoreResult<Vec<OhlcPoint>> {
+        sqlx::query_as::<_, OhlcPoint>(
+            "SELECT timestamp, open, high, low, value AS close
+             FROM index_snapshots WHERE timestamp >= $1 AND timestamp < $2
+             ORDER BY timestamp",
+        )
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn span(&self) -> StoreResult<Option<(String, String)>> {
+        let (min, max) = sqlx::query_as::<_, (Option<String>, Option<String>)>(
+            "SELECT MIN(timestamp), MAX(timestamp) FROM index_snapshots",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(min.zip(max))
+    }
+
+    async fn upsert(&self, s: &IndexSnapshot) -> StoreResult<()> {
+        sqlx::query(
+            "INSERT INTO index_snapshots (value, daily_change, daily_change_pct, timestamp)
+             VALUES ($1, $2, $3, $4)
+             ON CONFLICT(timestamp) DO UPDATE SET
+               value = excluded.value,
+               daily_change = excluded.daily_change,
+               daily_change_pct = excluded.daily_change_pct",
+        )
+        .persistent(false)
+        .bind(s.value)
+        .bind(s.daily_change)
+        .bind(s.daily_change_pct)
+        .bind(&s.timestamp)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn data_version(&self) -> StoreResult<i64> {
+        let (version,) = sqlx::query_as::<_, (i64,)>("SELECT version FROM data_version")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(version)
+    }
+
+    async fn bump_data_version(&self) -> StoreResult<()> {
+        sqlx::query("UPDATE data_version SET version = version + 1")
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ProfileStore for SqlStore {
+    async fn get(&self, symbol: &str) -> StoreResult<Option<StockProfile>> {
+        sqlx::query_as::<_, StockProfile>(
+            "SELECT name, exchange, industry, weburl, logo, country FROM stock_profiles WHERE symbol = $1",
+        )
+        .bind(symbol)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn all(&self) -> StoreResult<Vec<(String, StockProfile)>> {
+        let rows = sqlx::query_as::<_, ProfileRow>(
+            "SELECT symbol, name, exchange, industry, weburl, logo, country FROM stock_profiles",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().map(|r| (r.symbol, r.profile)).collect())
+    }
+
+    async fn upsert(&self, symbol: &str, p: &StockProfile, updated_at: &str) -> StoreResult<()> {
+        sqlx::query(
+            "INSERT INTO stock_profiles (symbol, name, exchange, industry, weburl, logo, country, updated_at)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+             ON CONFLICT(symbol) DO UPDATE SET
+               name = excluded.name,
+               exchange = excluded.exchange,
+               industry = excluded.industry,
+               weburl = excluded.weburl,
+               logo = excluded.logo,
+               country = excluded.country,
+               updated_at = excluded.updated_at",
+        )
+        .bind(symbol)
+        .bind(&p.name)
+        .bind(&p.exchange)
+        .bind(&p.industry)
+        .bind(&p.weburl)
+        .bind(&p.logo)
+        .bind(&p.country)
+        .bind(updated_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
+
+#[derive(FromRow)]
+struct ProfileRow {
+    symbol: String,
+    #[sqlx(flatten)]
+    profile: StockProfile,
+}
+
+#[derive(FromRow)]
+struct FetchRunRow {
+    id: i64,
+    kind: String,
+    started_at: String,
+    finished_at: Option<String>,
+    symbols: String,
+    succeeded: i64,
+    failed: i64,
+    errors: String,
+}
+
+impl From<FetchRunRow> for FetchRun {
+    fn from(r: FetchRunRow) -> Self {
+        FetchRun {
+            id: r.id,
+            kind: r.kind,
+            status: run_status(r.finished_at.is_some(), r.succeeded, r.failed),
+            started_at: r.started_at,
+            finished_at: r.finished_at,
+            symbols: serde_json::from_str(&r.symbols).unwrap_or_default(),
+            succeeded: r.succeeded,
+            failed: r.failed,
+            errors: serde_json::from_str(&r.errors).unwrap_or_default(),
+        }
+    }
+}
+
+#[async_trait]
+impl FetchRunStore for SqlStore {
+    async fn start(&self, kind: &str, symbols: &[String], started_at: &str) -> StoreResult<i64> {
+        let symbols = serde_json::to_string(symbols).unwrap_or_else(|_| "[]".to_string());
+        let (id,) = sqlx::query_as::<_, (i64,)>(
+            "INSERT INTO fetch_runs (kind, started_at, symbols) VALUES ($1, $2, $3) RETURNING id",
+        )
+        .bind(kind)
+        .bind(started_at)
+        .bind(symbols)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(id)
+    }
+
+    async fn finish(
+        &self,
+        id: i64,
+        succeeded: i64,
+        failed: i64,
+        errors: &[String],
+        finished_at: &str,
+    ) -> StoreResult<()> {
+        let errors = serde_json::to_string(errors).unwrap_or_else(|_| "[]".to_string());
+        sqlx::query(
+            "UPDATE fetch_runs SET finished_at = $1, succeeded = $2, failed = $3, errors = $4
+             WHERE id = $5",
+        )
+        .bind(finished_at)
+        .bind(succeeded)
+        .bind(failed)
+        .bind(errors)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn recent(&self, kind: Option<&str>, limit: i64) -> StoreResult<Vec<FetchRun>> {
+        let rows =
+            match kind {
+                Some(kind) => sqlx::query_as::<_, FetchRunRow>(
+                    "SELECT id, kind, started_at, finished_at, symbols, succeeded, failed, errors
+                     FROM fetch_runs WHERE kind = $1 ORDER BY id DESC LIMIT $2",
+                )
+                .bind(kind)
+                .bind(limit)
+                .fetch_all(&self.pool)
+                .await?,
+                None => sqlx::query_as::<_, FetchRunRow>(
+                    "SELECT id, kind, started_at, finished_at, symbols, succeeded, failed, errors
+                     FROM fetch_runs ORDER BY id DESC LIMIT $1",
+                )
+                .bind(limit)
+                .fetch_all(&self.pool)
+                .await?,
+            };
+        Ok(rows.into_iter().map(FetchRun::from).collect())
+    }
+}
+
+#[async_trait]
+impl ApiKeyStore for SqlStore {
+    async fn create(
+        &self,
+        name: &str,
+        prefix: &str,
+        key_hash: &str,
+        role: &str,
+        created_at: &str,
+    ) -> StoreResult<i64> {
+        let (id,) = sqlx::query_as::<_, (i64,)>(
+            "INSERT INTO api_keys (name, prefix, key_hash, role, created_at)
+             VALUES ($1, $2, $3, $4, $5) RETURNING id",
+        )
+        .bind(name)
+        .bind(prefix)
+        .bind(key_hash)
+        .bind(role)
+        .bind(created_at)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(id)
+    }
+
+    async fn find_active(&self, key_hash: &str) -> StoreResult<Option<ApiKey>> {
+        sqlx::query_as::<_, ApiKey>(
+            "SELECT id, name, prefix, role, created_at, last_used_at, revoked_at
+             FROM api_keys WHERE key_hash = $1 AND revoked_at IS NULL",
+        )
+        .bind(key_hash)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn touch(&self, id: i64, used_at: &str) -> StoreResult<()> {
+        sqlx::query("UPDATE api_keys SET last_used_at = $1 WHERE id = $2")
+            .bind(used_at)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn revoke(&self, id: i64, revoked_at: &str) -> StoreResult<bool> {
+        let result =
+            sqlx::query("UPDATE api_keys SET revoked_at = $1 WHERE id = $2 AND revoked_at IS NULL")
+                .bind(revoked_at)
+                .bind(id)
+                .execute(&self.pool)
+                .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn all(&self) -> StoreResult<Vec<ApiKey>> {
+        sqlx::query_as::<_, ApiKey>(
+            "SELECT id, name, prefix, role, created_at, last_used_at, revoked_at
+             FROM api_keys ORDER BY id",
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+}
@@ -0,0 +1,374 @@
+//! One suite for every store implementation. SQLite and the in-memory store
+//! always run; Postgres runs when `DATABASE_URL` points at a Postgres server.
+
+use super::Stores;
+use crate::db::ScratchDb;
+use crate::models::{IndexSnapshot, PriceBar, PriceRecord, StockProfile};
+
+fn price(symbol: &str, price: f64, market_cap: Option<f64>, timestamp: &str) -> PriceRecord {
+    PriceRecord {
+        symbol: symbol.to_string(),
+        price,
+        change: None,
+        change_pct: None,
+        market_cap,
+        timestamp: timestamp.to_string(),
+    }
+}
+
+fn snapshot(value: f64, daily_change: Option<f64>, timestamp: &str) -> IndexSnapshot {
+    IndexSnapshot {
+        value,
+        daily_change,
+        daily_change_pct: daily_change.map(|c| c / value * 100.0),
+        timestamp: timestamp.to_string(),
+    }
+}
+
+async fn prices(stores: &Stores) {
+    let p = &stores.prices;
+    p.upsert(&price(
+        "NVDA",
+        100.0,
+        Some(1e12),
+        "2024-03-11T14:00:00+00:00",
+    ))
+    .await
+    .unwrap();
+    // A NULL float followed by a value through the same statement.
+    p.upsert(&price("NVDA", 101.0, None, "2024-03-11T15:00:00+00:00"))
+        .await
+        .unwrap();
+    p.upsert(&PriceRecord {
+        change: Some(2.0),
+        change_pct: Some(2.0),
+        ..price("NVDA", 102.0, Some(2e12), "2024-03-11T16:00:00+00:00")
+    })
+    .await
+    .unwrap();
+    p.upsert(&price("AMD", 50.0, None, "2024-03-11T14:00:00+00:00"))
+        .await
+        .unwrap();
+
+    let latest = p.latest("NVDA").await.unwrap().unwrap();
+    assert_eq!(latest.price, 102.0);
+    assert_eq!(latest.change, Some(2.0));
+    assert!(p.latest("ZZZ").await.unwrap().is_none());
+
+    let mut all = p.latest_all().await.unwrap();
+    all.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+    let all: Vec<_> = all.iter().map(|r| (r.symbol.as_str(), r.price)).collect();
+    assert_eq!(all, [("AMD", 50.0), ("NVDA", 102.0)]);
+
+    let series: Vec<f64> = p
+        .series("NVDA")
+        .await
+        .unwrap()
+        .iter()
+        .map(|r| r.price)
+        .collect();
+    assert_eq!(series, [100.0, 101.0, 102.0]);
+    let history: Vec<f64> = p
+        .history("NVDA", 2)
+        .await
+        .unwrap()
+        .iter()
+        .map(|r| r.price)
+        .collect();
+    assert_eq!(history, [102.0, 101.0]);
+    let range = p
+        .range(
+            "NVDA",
+            "2024-03-11T15:00:00+00:00",
+            "2024-03-11T16:00:00+00:00",
+        )
+        .await
+        .unwrap();
+    assert_eq!(range.len(), 1);
+    assert_eq!(range[0].price, 101.0);
+    let before = p.before("NVDA", "2024-03-11T15:00:00+00:00").await.unwrap();
+    assert_eq!(before.map(|r| r.price), Some(100.0));
+    assert!(p
+        .before("NVDA", "2024-03-11T14:00:00+00:00")
+        .await
+        .unwrap()
+        .is_none());
+    let after = p.after("NVDA", "2024-03-11T15:00:00+00:00").await.unwrap();
+    assert_eq!(after.map(|r| r.price), Some(101.0));
+    assert!(p
+        .after("NVDA", "2024-03-11T16:00:01+00:00")
+        .await
+        .unwrap()
+        .is_none());
+
+    // Replacing a row keeps one row per (symbol, timestamp).
+    p.upsert(&price(
+        "NVDA",
+        103.0,
+        Some(2e12),
+        "2024-03-11T16:00:00+00:00",
+    ))
+    .await
+    .unwrap();
+    assert_eq!(p.series("NVDA").await.unwrap().len(), 3);
+    assert_eq!(p.latest_market_cap("NVDA").await.unwrap(), Some(2e12));
+
+    p.set_latest_market_cap("AMD", 3e11).await.unwrap();
+    assert_eq!(
+        p.latest("AMD").await.unwrap().unwrap().market_cap,
+        Some(3e11)
+    );
+}
+
+async fn cycles(stores: &Stores) {
+    let p = &stores.prices;
+    p.record_cycle(&[price("TSM", 150.0, Some(8e11), "2024-03-11T14:00:00+00:00")])
+        .await
+        .unwrap();
+    p.record_cycle(&[price("TSM", 155.0, None, "2024-03-11T15:00:00+00:00")])
+        .await
+        .unwrap();
+
+    let latest = p.latest("TSM").await.unwrap().unwrap();
+    assert_eq!(latest.price, 155.0);
+    assert_eq!(latest.market_cap, Some(8e11), "market cap carries forward");
+    assert_eq!(
+        p.base_price("TSM").await.unwrap(),
+        Some(150.0),
+        "base is set once"
+    );
+
+    p.insert_base_price("TSM", 1.0, "2024-03-12T00:00:00+00:00")
+        .await
+        .unwrap();
+    assert_eq!(p.base_price("TSM").await.unwrap(), Some(150.0));
+}
+
+async fn bars(stores: &Stores) {
+    let p = &stores.prices;
+    p.upsert(&price(
+        "ARM",
+        120.0,
+        Some(1.2e11),
+        "2024-03-08T21:00:00+00:00",
+    ))
+    .await
+    .unwrap();
+    let bar = |close: f64, change: Option<f64>, timestamp: &str| PriceBar {
+        symbol: "ARM".to_string(),
+        timestamp: timestamp.to_string(),
+        open: Some(close - 1.0),
+        high: Some(close + 1.0),
+        low: None,
+        close,
+        volume: Some(1000.0),
+        change,
+        change_pct: None,
+        market_cap: Some(9.9e10),
+    };
+    p.upsert_bars(&[
+        bar(121.0, None, "2024-03-08T21:00:00+00:00"),
+        bar(125.0, Some(4.0), "2024-03-11T20:00:00+00:00"),
+    ])
+    .await
+    .unwrap();
+
+    let series = p.series("ARM").await.unwrap();
+    assert_eq!(series.len(), 2);
+    assert_eq!(series[0].price, 121.0);
+    assert_eq!(
+        series[0].market_cap,
+        Some(1.2e11),
+        "stored market cap is kept"
+    );
+    assert_eq!(series[1].change, Some(4.0));
+    assert_eq!(series[1].market_cap, Some(9.9e10));
+}
+
+async fn snapshots(stores: &Stores) {
+    let s = &stores.snapshots;
+    assert!(s.latest().await.unwrap().is_none());
+    assert!(s.span().await.unwrap().is_none());
+
+    s.upsert(&snapshot(1000.0, None, "2024-03-08T21:00:00+00:00"))
+        .await
+        .unwrap();
+    s.upsert(&snapshot(1010.0, Some(10.0), "2024-03-11T20:00:00+00:00"))
+        .await
+        .unwrap();
+    s.upsert(&snapshot(1012.0, Some(12.0), "2024-03-11T20:00:00+00:00"))
+        .await
+        .unwrap();
+
+    let latest = s.latest().await.unwrap().unwrap();
+    assert_eq!(latest.value, 1012.0);
+    assert_eq!(latest.daily_change, Some(12.0));
+    let values: Vec<f64> = s
+        .history(10)
+        .await
+        .unwrap()
+        .iter()
+        .map(|s| s.value)
+        .collect();
+    assert_eq!(values, [1012.0, 1000.0]);
+    let range = s
+        .range("2024-03-08T00:00:00+00:00", "2024-03-09T00:00:00+00:00")
+        .await
+        .unwrap();
+    assert_eq!(range.len(), 1);
+    assert_eq!(range[0].daily_change, None);
+    assert_eq!(
+        s.span().await.unwrap(),
+        Some((
+            "2024-03-08T21:00:00+00:00".to_string(),
+            "2024-03-11T20:00:00+00:00".to_string()
+        ))
+    );
+
+    let version = s.data_version().await.unwrap();
+    s.bump_data_version().await.unwrap();
+    assert_eq!(s.data_version().await.unwrap(), version + 1);
+}
+
+async fn profiles(stores: &Stores) {
+    let p = &stores.profiles;
+    let profile = StockProfile {
+        name: Some("NVIDIA".to_string()),
+        country: Some("US".to_string()),
+        ..Default::default()
+    };
+    p.upsert("NVDA", &profile, "2024-03-11T00:00:00+00:00")
+        .await
+        .unwrap();
+    p.upsert(
+        "NVDA",
+        &StockProfile {
+            industry: Some("Semiconductors".to_string()),
+            ..profile
+        },
+        "2024-03-12T00:00:00+00:00",
+    )
+    .await
+    .unwrap();
+
+    let stored = p.get("NVDA").await.unwrap().unwrap();
+    assert_eq!(stored.name.as_deref(), Some("NVIDIA"));
+    assert_eq!(stored.industry.as_deref(), Some("Semiconductors"));
+    assert!(p.get("AMD").await.unwrap().is_none());
+    assert_eq!(p.all().await.unwrap().len(), 1);
+}
+
+async fn runs(stores: &Stores) {
+    let r = &stores.runs;
+    let symbols = vec!["NVDA".to_string(), "AMD".to_string()];
+    let quotes = r
+        .start("quotes", &symbols, "2024-03-11T14:00:00+00:00")
+        .await
+        .unwrap();
+    let profiles = r
+        .start("profiles", &symbols, "2024-03-11T14:01:00+00:00")
+        .await
+        .unwrap();
+
+    let running = r.recent(Some("quotes"), 10).await.unwrap();
+    assert_eq!(running[0].status, "running");
+
+    r.finish(
+        quotes,
+        1,
+        1,
+        &["AMD: timeout".to_string()],
+        "2024-03-11T14:02:00+00:00",
+    )
+    .await
+    .unwrap();
+    r.finish(profiles, 2, 0, &[], "2024-03-11T14:03:00+00:00")
+        .await
+        .unwrap();
+
+    let all = r.recent(None, 10).await.unwrap();
+    assert_eq!(
+        all.iter().map(|r| r.id).collect::<Vec<_>>(),
+        [profiles, quotes]
+    );
+    let quotes = &r.recent(Some("quotes"), 10).await.unwrap()[0];
+    assert_eq!(quotes.status, "partial");
+    assert_eq!(quotes.symbols, symbols);
+    assert_eq!(quotes.errors, ["AMD: timeout"]);
+    assert_eq!(r.recent(None, 1).await.unwrap().len(), 1);
+}
+
+async fn keys(stores: &Stores) {
+    let k = &stores.keys;
+    let id = k
+        .create(
+            "ops",
+            "aiidx_0123",
+            "hash-1",
+            "admin",
+            "2024-03-11T00:00:00+00:00",
+        )
+        .await
+        .unwrap();
+    k.create(
+        "ci",
+        "aiidx_4567",
+        "hash-2",
+        "read",
+        "2024-03-11T00:00:00+00:00",
+    )
+    .await
+    .unwrap();
+
+    let key = k.find_active("hash-1").await.unwrap().unwrap();
+    assert_eq!((key.id, key.role.as_str()), (id, "admin"));
+    assert!(k.find_active("nope").await.unwrap().is_none());
+
+    k.touch(id, "2024-03-11T01:00:00+00:00").await.unwrap();
+    let key = k.find_active("hash-1").await.unwrap().unwrap();
+    assert_eq!(
+        key.last_used_at.as_deref(),
+        Some("2024-03-11T01:00:00+00:00")
+    );
+
+    assert!(k.revoke(id, "2024-03-11T02:00:00+00:00").await.unwrap());
+    assert!(!k.revoke(id, "2024-03-11T03:00:00+00:00").await.unwrap());
+    assert!(k.find_active("hash-1").await.unwrap().is_none());
+
+    let all = k.all().await.unwrap();
+    assert_eq!(all.len(), 2);
+    assert_eq!(
+        all[0].revoked_at.as_deref(),
+        Some("2024-03-11T02:00:00+00:00")
+    );
+}
+
+async fn suite(stores: Stores) {
+    prices(&stores).await;
+    cycles(&stores).await;
+    bars(&stores).await;
+    snapshots(&stores).await;
+    profiles(&stores).await;
+    runs(&stores).await;
+    keys(&stores).await;
+}
+
+#[tokio::test]
+async fn memory() {
+    suite(Stores::memory()).await;
+}
+
+#[tokio::test]
+async fn sqlite() {
+    let db = ScratchDb::sqlite().await;
+    suite(Stores::sql(db.pool.clone())).await;
+}
+
+#[tokio::test]
+async fn postgres() {
+    let Some(db) = ScratchDb::postgres().await else {
+        eprintln!("DATABASE_URL is not a Postgres URL; skipping");
+        return;
+    };
+    suite(Stores::sql(db.pool.clone())).await;
+}
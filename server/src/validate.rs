@@ -0,0 +1,492 @@
+//! Checks on `stocks.toml` beyond what deserialization catches.
+//!
+//! The file is parsed a second time with `toml::Spanned` fields so every
+//! diagnostic can point at the line it is about. Errors stop the server from
+//! starting (or a reload from applying); warnings are only logged.
+
+use chrono::{NaiveDate, NaiveTime};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use toml::Spanned;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// 1-based line in the file, when the problem has a location.
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        match self.line {
+            Some(line) => write!(f, "line {}: {}: {}", line, severity, self.message),
+            None => write!(f, "{}: {}", severity, self.message),
+        }
+    }
+}
+
+/// Everything found in one file, in line order.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Report {
+    pub fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Warning)
+    }
+}
+
+#[derive(Deserialize)]
+struct RawConfig {
+    settings: RawSettings,
+    sectors: HashMap<Spanned<String>, RawSector>,
+    benchmarks: RawBenchmarks,
+    #[serde(default)]
+    exchanges: HashMap<Spanned<String>, RawExchange>,
+}
+
+#[derive(Deserialize)]
+struct RawSettings {
+    base_value: Spanned<f64>,
+    market_cap_weight_pct: Spanned<u32>,
+    reference_exchange: Option<Spanned<String>>,
+}
+
+// Fields that are deserialized but not checked further are kept so that
+// type errors in them are reported with a line too.
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct RawSector {
+    label: String,
+    symbols: Spanned<Vec<Spanned<String>>>,
+    color: Option<Spanned<String>>,
+    short_label: Option<String>,
+    description: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RawBenchmarks {
+    symbols: Vec<Spanned<String>>,
+}
+
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct RawExchange {
+    timezone: Tz,
+    open: Spanned<NaiveTime>,
+    close: Spanned<NaiveTime>,
+    #[serde(default)]
+    holidays: Vec<NaiveDate>,
+    #[serde(default)]
+    symbols: Vec<Spanned<String>>,
+}
+
+/// Validate a `stocks.toml` document.
+pub fn check(text: &str) -> Report {
+    let mut checker = Checker {
+        text,
+        diagnostics: Vec::new(),
+    };
+    match toml::from_str::<RawConfig>(text) {
+        Ok(raw) => checker.run(&raw),
+        Err(e) => checker.diagnostics.push(parse_error(text, &e)),
+    }
+    let mut diagnostics = checker.diagnostics;
+    diagnostics.sort_by_key(|d| d.line);
+    Report { diagnostics }
+}
+
+/// A TOML syntax or type error, located in `text`.
+pub fn parse_error(text: &str, e: &toml::de::Error) -> Diagnostic {
+    Diagnostic {
+        severity: Severity::Error,
+        line: e.span().map(|s| line_of(text, s.start)),
+        message: e.message().trim().to_string(),
+    }
+}
+
+/// `#rgb` or `#rrggbb`, the forms every chart library accepts.
+fn is_hex_color(s: &str) -> bool {
+    s.strip_prefix('#')
+        .is_some_and(|hex| matches!(hex.len(), 3 | 6) && hex.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// 1-based line of a byte offset.
+fn line_of(text: &str, offset: usize) -> usize {
+    text[..offset.min(text.len())].matches('\n').count() + 1
+}
+
+struct Checker<'a> {
+    text: &'a str,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker<'_> {
+    fn run(&mut self, raw: &RawConfig) {
+        self.settings(&raw.settings, &raw.exchanges);
+
+        // Sector tables come back unordered; walk them in file order so the
+        // first listing of a symbol is the one later duplicates point at.
+        let mut sectors: Vec<_> = raw.sectors.iter().collect();
+        sectors.sort_by_key(|(key, _)| key.span().start);
+        if sectors.is_empty() {
+            self.push(Severity::Error, None, "no sectors are defined".to_string());
+        }
+
+        let mut seen: HashMap<&str, (&str, usize)> = HashMap::new();
+        for (key, sector) in sectors {
+            if sector.symbols.get_ref().is_empty() {
+                let line = self.line(sector.symbols.span().start);
+                self.push(
+                    Severity::Error,
+                    Some(line),
+                    format!("sector '{}' has no symbols", key.get_ref()),
+                );
+            }
+            if let Some(color) = &sector.color {
+                if !is_hex_color(color.get_ref()) {
+                    let line = self.line(color.span().start);
+                    self.push(
+                        Severity::Error,
+                        Some(line),
+                        format!(
+                            "color '{}' of sector '{}' is not a hex color like #00d4ff",
+                            color.get_ref(),
+                            key.get_ref()
+                        ),
+                    );
+                }
+            }
+            for symbol in sector.symbols.get_ref() {
+                let line = self.symbol(symbol);
+                match seen.get(symbol.get_ref().as_str()) {
+                    Some((other, first)) => self.push(
+                        Severity::Error,
+                        Some(line),
+                        format!(
+                            "'{}' is already listed in sector '{}' (line {}); it would be counted twice",
+                            symbol.get_ref(),
+                            other,
+                            first
+                        ),
+                    ),
+                    None => {
+                        seen.insert(symbol.get_ref(), (key.get_ref(), line));
+                    }
+                }
+            }
+        }
+
+        let mut benchmarks: HashMap<&str, usize> = HashMap::new();
+        for symbol in &raw.benchmarks.symbols {
+            let line = self.symbol(symbol);
+            if let Some(first) = benchmarks.insert(symbol.get_ref(), line) {
+                self.push(
+                    Severity::Warning,
+                    Some(line),
+                    format!(
+                        "benchmark '{}' is already listed (line {})",
+                        symbol.get_ref(),
+                        first
+                    ),
+                );
+            }
+            if let Some((sector, first)) = seen.get(symbol.get_ref().as_str()) {
+                self.push(
+                    Severity::Warning,
+                    Some(line),
+                    format!(
+                        "benchmark '{}' is also a constituent of sector '{}' (line {}) and will be reported as a constituent",
+                        symbol.get_ref(),
+                        sector,
+                        first
+                    ),
+                );
+            }
+        }
+
+        self.exchanges(&raw.exchanges, &seen, &benchmarks);
+    }
+
+    fn settings(
+        &mut self,
+        settings: &RawSettings,
+        exchanges: &HashMap<Spanned<String>, RawExchange>,
+    ) {
+        let pct = &settings.market_cap_weight_pct;
+        if *pct.get_ref() > 100 {
+            let line = self.line(pct.span().start);
+            self.push(
+                Severity::Error,
+                Some(line),
+                format!(
+                    "market_cap_weight_pct is {}, but must be between 0 and 100",
+                    pct.get_ref()
+                ),
+            );
+        }
+
+        let base = &settings.base_value;
+        if !base.get_ref().is_finite() || *base.get_ref() <= 0.0 {
+            let line = self.line(base.span().start);
+            self.push(
+                Severity::Error,
+                Some(line),
+                format!("base_value is {}, but must be positive", base.get_ref()),
+            );
+        }
+
+        if let Some(reference) = &settings.reference_exchange {
+            let name = reference.get_ref();
+            // "US" is built in when not configured.
+            let defined = name == "US" || exchanges.keys().any(|k| k.get_ref() == name);
+            if !defined {
+                let line = self.line(reference.span().start);
+                self.push(
+                    Severity::Error,
+                    Some(line),
+                    format!("reference_exchange '{}' is not defined", name),
+                );
+            }
+        }
+    }
+
+    fn exchanges(
+        &mut self,
+        exchanges: &HashMap<Spanned<String>, RawExchange>,
+        constituents: &HashMap<&str, (&str, usize)>,
+        benchmarks: &HashMap<&str, usize>,
+    ) {
+        let mut exchanges: Vec<_> = exchanges.iter().collect();
+        exchanges.sort_by_key(|(key, _)| key.span().start);
+
+        let mut listed: HashMap<&str, (&str, usize)> = HashMap::new();
+        for (key, exchange) in exchanges {
+            if exchange.open.get_ref() >= exchange.close.get_ref() {
+                let line = self.line(exchange.close.span().start);
+                self.push(
+                    Severity::Error,
+                    Some(line),
+                    format!(
+                        "exchange '{}' closes at {} but opens at {}",
+                        key.get_ref(),
+                        exchange.close.get_ref().format("%H:%M"),
+                        exchange.open.get_ref().format("%H:%M")
+                    ),
+                );
+            }
+            for symbol in &exchange.symbols {
+                let line = self.symbol(symbol);
+                let name = symbol.get_ref().as_str();
+                if let Some((other, first)) = listed.get(name) {
+                    self.push(
+                        Severity::Warning,
+                        Some(line),
+                        format!(
+                            "'{}' is already listed on exchange '{}' (line {}); the first listing wins",
+                            name, other, first
+                        ),
+                    );
+                } else {
+                    listed.insert(name, (key.get_ref(), line));
+                }
+                if !constituents.contains_key(name) && !benchmarks.contains_key(name) {
+                    self.push(
+                        Severity::Warning,
+                        Some(line),
+                        format!(
+                            "'{}' on exchange '{}' is not a constituent or benchmark",
+                            name,
+                            key.get_ref()
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Check a symbol's spelling and return its line.
+    fn symbol(&mut self, symbol: &Spanned<String>) -> usize {
+        let line = self.line(symbol.span().start);
+        let name = symbol.get_ref();
+        if name.trim().is_empty() {
+            self.push(Severity::Error, Some(line), "empty symbol".to_string());
+        } else if *name != name.to_uppercase() {
+            // Quotes are stored and requests matched under the upper-case
+            // symbol, so any other spelling would never match.
+            self.push(
+                Severity::Error,
+                Some(line),
+                format!("'{}' must be upper case ('{}')", name, name.to_uppercase()),
+            );
+        }
+        line
+    }
+
+    fn line(&self, offset: usize) -> usize {
+        line_of(self.text, offset)
+    }
+
+    fn push(&mut self, severity: Severity, line: Option<usize>, message: String) {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            line,
+            message,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VALID: &str = r#"
+[settings]
+base_value = 1000.0
+market_cap_weight_pct = 50
+
+[sectors.chips]
+label = "Chips"
+symbols = ["NVDA", "AMD"]
+
+[sectors.cloud]
+label = "Cloud"
+symbols = ["MSFT"]
+
+[benchmarks]
+symbols = ["SPY"]
+"#;
+
+    /// (severity, line, message) of every diagnostic.
+    fn diagnostics(text: &str) -> Vec<(Severity, Option<usize>, String)> {
+        check(text)
+            .diagnostics
+            .into_iter()
+            .map(|d| (d.severity, d.line, d.message))
+            .collect()
+    }
+
+    #[test]
+    fn valid_config_has_no_diagnostics() {
+        assert!(diagnostics(VALID).is_empty());
+    }
+
+    #[test]
+    fn duplicate_constituents_point_at_the_first_listing() {
+        let text = VALID.replace(r#"["MSFT"]"#, r#"["MSFT", "NVDA"]"#);
+        let found = diagnostics(&text);
+        assert_eq!(found.len(), 1);
+        let (severity, line, message) = &found[0];
+        assert_eq!((*severity, *line), (Severity::Error, Some(12)));
+        assert!(message.contains("sector 'chips' (line 8)"), "{}", message);
+    }
+
+    #[test]
+    fn weight_pct_above_100_is_an_error() {
+        let text = VALID.replace("market_cap_weight_pct = 50", "market_cap_weight_pct = 150");
+        let found = diagnostics(&text);
+        assert_eq!(found.len(), 1);
+        assert_eq!((found[0].0, found[0].1), (Severity::Error, Some(4)));
+    }
+
+    #[test]
+    fn empty_sectors_are_errors() {
+        let text = VALID.replace(r#"["MSFT"]"#, "[]");
+        let found = diagnostics(&text);
+        assert_eq!(found.len(), 1);
+        assert_eq!((found[0].0, found[0].1), (Severity::Error, Some(12)));
+        assert!(found[0].2.contains("'cloud' has no symbols"));
+    }
+
+    #[test]
+    fn benchmarks_that_are_constituents_are_warnings() {
+        let text = VALID.replace(r#"["SPY"]"#, r#"["SPY", "AMD"]"#);
+        let found = diagnostics(&text);
+        assert_eq!(found.len(), 1);
+        assert_eq!((found[0].0, found[0].1), (Severity::Warning, Some(15)));
+        assert!(found[0].2.contains("sector 'chips' (line 8)"));
+    }
+
+    #[test]
+    fn lower_case_symbols_are_errors() {
+        let text = VALID.replace(r#""AMD""#, r#""amd""#);
+        let found = diagnostics(&text);
+        assert_eq!(found.len(), 1);
+        assert_eq!((found[0].0, found[0].1), (Severity::Error, Some(8)));
+    }
+
+    #[test]
+    fn diagnostics_are_sorted_by_line() {
+        let text = VALID
+            .replace("market_cap_weight_pct = 50", "market_cap_weight_pct = 150")
+            .replace(r#"["SPY"]"#, r#"["SPY", "SPY"]"#)
+            .replace(r#"["MSFT"]"#, "[]");
+        let lines: Vec<Option<usize>> = diagnostics(&text).iter().map(|d| d.1).collect();
+        assert_eq!(lines, [Some(4), Some(12), Some(15)]);
+    }
+
+    #[test]
+    fn symbols_on_two_exchanges_keep_the_first_listing() {
+        let text = format!(
+            "{}{}",
+            VALID,
+            r#"
+[exchanges.XNAS]
+timezone = "America/New_York"
+open = "09:30:00"
+close = "16:00:00"
+symbols = ["NVDA"]
+
+[exchanges.XLON]
+timezone = "Europe/London"
+open = "08:00:00"
+close = "16:30:00"
+symbols = ["NVDA"]
+"#
+        );
+        let found = diagnostics(&text);
+        assert_eq!(found.len(), 1);
+        assert_eq!((found[0].0, found[0].1), (Severity::Warning, Some(27)));
+        assert!(found[0].2.contains("exchange 'XNAS' (line 21)"));
+
+        let config = crate::config::StocksConfig::parse("test".to_string(), &text).unwrap();
+        assert_eq!(
+            config.exchange_for("NVDA").timezone,
+            chrono_tz::America::New_York
+        );
+    }
+
+    #[test]
+    fn syntax_errors_have_a_line() {
+        let text = VALID.replace("label = \"Cloud\"", "label = ");
+        let found = diagnostics(&text);
+        assert_eq!(found.len(), 1);
+        assert_eq!((found[0].0, found[0].1), (Severity::Error, Some(11)));
+    }
+}